@@ -13,6 +13,19 @@ pub struct GithubArgs {
     pub repository: String,
     /// Branch or tag name where an action was triggered.
     pub ref_name: String,
+    /// Commit hash that triggered the workflow.
+    pub sha: String,
+    /// Base URL of the GitHub server like `https://github.com`.
+    #[serde(default = "default_server_url")]
+    pub server_url: String,
+    /// Unique number of the current workflow run within the repository.
+    pub run_id: String,
+    /// Number of the current attempt of a workflow run, increasing on re-runs.
+    pub run_attempt: String,
+}
+
+fn default_server_url() -> String {
+    "https://github.com".to_owned()
 }
 
 /// Load all commonly present environment variables.
@@ -66,14 +66,14 @@ pub fn download_asset(token: &str, asset: &Asset) -> Result<AssetReader> {
     Ok(AssetReader(reader))
 }
 
-/// Upload an asset to an existing release.
+/// Upload an asset to an existing release, returning the newly created asset.
 pub fn upload_asset(
     token: &str,
     repo: &str,
     release: ReleaseId,
     name: &str,
     file: &[u8],
-) -> Result<()> {
+) -> Result<Asset> {
     ureq::post(&format!(
         "https://uploads.github.com/repos/{}/releases/{}/assets?name={}",
         repo, release.0, name
@@ -81,9 +81,9 @@ pub fn upload_asset(
     .set("Authorization", &format!("Bearer {token}"))
     .set("Accept", "application/vnd.github.v3+json")
     .set("Content-Type", "text/plain")
-    .send_bytes(file)?;
-
-    Ok(())
+    .send_bytes(file)?
+    .into_json()
+    .map_err(Into::into)
 }
 
 /// Delete an already existing asset from a release.
@@ -7,4 +7,5 @@
 pub mod env;
 pub mod glob;
 pub mod http;
+pub mod output;
 pub mod tracing;
@@ -0,0 +1,74 @@
+//! Step outputs, that later steps of a workflow can read.
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{ensure, Context, Result};
+
+/// Set an output parameter for the current step.
+///
+/// The value is written to the file that the runner provides through the `GITHUB_OUTPUT`
+/// environment variable. Outside of a runner, where the variable is missing, nothing happens.
+pub fn set(name: &str, value: &str) -> Result<()> {
+    match std::env::var_os("GITHUB_OUTPUT") {
+        Some(path) => write(&PathBuf::from(path), name, value),
+        None => Ok(()),
+    }
+}
+
+fn write(path: &Path, name: &str, value: &str) -> Result<()> {
+    const DELIMITER: &str = "ghadelimiter_7b4f1a2c";
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed opening output file `{}`", path.display()))?;
+
+    if value.contains('\n') {
+        ensure!(
+            !value.contains(DELIMITER),
+            "output `{name}` contains the reserved delimiter"
+        );
+        writeln!(file, "{name}<<{DELIMITER}\n{value}\n{DELIMITER}")?;
+    } else {
+        writeln!(file, "{name}={value}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn write_outputs() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("github-output-{}", std::process::id()));
+        fs::remove_file(&path).ok();
+
+        write(&path, "rustc_version", "1.76.0")?;
+        write(&path, "notes", "first\nsecond")?;
+
+        let content = fs::read_to_string(&path)?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(
+            vec![
+                "rustc_version=1.76.0",
+                "notes<<ghadelimiter_7b4f1a2c",
+                "first",
+                "second",
+                "ghadelimiter_7b4f1a2c",
+            ],
+            content.lines().collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+}
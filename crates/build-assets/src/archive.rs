@@ -1,47 +1,535 @@
-use std::{fs::File, io::Cursor};
+use std::{
+    env,
+    fmt::{self, Display},
+    fs::{self, File},
+    io::{self, Cursor, Write},
+    ops::RangeInclusive,
+    process::Command,
+};
 
-use anyhow::Result;
-use camino::Utf8Path;
-use flate2::{write::GzEncoder, Compression};
-use tar::{Builder as TarBuilder, Header as TarHeader};
+use anyhow::{bail, ensure, Context, Result};
+use bzip2::write::BzEncoder;
+use camino::{Utf8Path, Utf8PathBuf};
+use flate2::{Compression, GzBuilder};
+use serde::{Deserialize, Serialize};
+use tar::{Builder as TarBuilder, EntryType, Header as TarHeader};
 use target_lexicon::Triple;
-use zip::{write::FileOptions as ZipFileOptions, CompressionMethod, ZipWriter};
+use time::OffsetDateTime;
+use xz2::write::XzEncoder;
+use zip::{write::FileOptions as ZipFileOptions, CompressionMethod, DateTime, ZipWriter};
 
-pub fn tar_gz(file: &Utf8Path, name: &str, target: &Triple) -> Result<(String, Vec<u8>)> {
-    let archive_name = format!("{name}-{target}.tar.gz");
+use crate::triple::TripleExt;
 
-    let builder = GzEncoder::new(Vec::new(), Compression::best());
-    let mut builder = TarBuilder::new(builder);
+/// Files to put into an archive, as pairs of the source location and the path inside the archive.
+pub type Entries<'a> = [(&'a Utf8Path, &'a str)];
 
+/// File format that the binaries are published in.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "tar.xz")]
+    TarXz,
+    #[serde(rename = "tar.zst")]
+    TarZst,
+    #[serde(rename = "tar.bz2")]
+    TarBz2,
+    #[serde(rename = "zip")]
+    Zip,
+    /// The plain file without any archive around it, which only works for a single binary.
+    #[serde(rename = "raw")]
+    Raw,
+}
+
+impl ArchiveFormat {
+    /// Format that is common for the target's operating system, which is `zip` for Windows and
+    /// `tar.gz` for everything else.
+    #[must_use]
+    pub fn for_target(target: &Triple) -> Self {
+        if target.is_windows() {
+            Self::Zip
+        } else {
+            Self::TarGz
+        }
+    }
+
+    /// Ensure the compression level is supported by the format.
+    pub fn check_level(self, level: Option<u32>) -> Result<()> {
+        let Some(level) = level else {
+            return Ok(());
+        };
+
+        let Some((levels, _)) = self.levels() else {
+            bail!("the {self} format doesn't support compression levels");
+        };
+
+        ensure!(
+            levels.contains(&level),
+            "compression level {level} is out of the range {}..={} for the {self} format",
+            levels.start(),
+            levels.end()
+        );
+
+        Ok(())
+    }
+
+    /// Range of supported compression levels, and the default level.
+    fn levels(self) -> Option<(RangeInclusive<u32>, u32)> {
+        match self {
+            Self::TarGz => Some((0..=9, 9)),
+            Self::TarXz | Self::Zip => Some((0..=9, 6)),
+            Self::TarZst => Some((1..=22, 19)),
+            Self::TarBz2 => Some((1..=9, 9)),
+            Self::Raw => None,
+        }
+    }
+}
+
+impl Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::TarGz => "tar.gz",
+            Self::TarXz => "tar.xz",
+            Self::TarZst => "tar.zst",
+            Self::TarBz2 => "tar.bz2",
+            Self::Zip => "zip",
+            Self::Raw => "raw",
+        })
+    }
+}
+
+/// Timestamp for all files in the archives, following the [reproducible builds] convention of the
+/// `SOURCE_DATE_EPOCH` variable. If it isn't set, the commit time of the given revision is used.
+///
+/// [reproducible builds]: https://reproducible-builds.org/docs/source-date-epoch/
+pub fn source_date(rev: &str) -> Result<OffsetDateTime> {
+    let epoch = match env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch,
+        Err(_) => commit_time(rev).context(
+            "failed getting the timestamp for archived files, set `SOURCE_DATE_EPOCH` if the \
+             commit isn't available",
+        )?,
+    };
+
+    let epoch = epoch
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid source date `{}`", epoch.trim()))?;
+
+    Ok(OffsetDateTime::from_unix_timestamp(epoch)?)
+}
+
+fn commit_time(rev: &str) -> Result<String> {
+    let output = Command::new("git")
+        .args(["show", "--no-patch", "--format=%ct", rev])
+        .output()
+        .context("failed running git")?;
+    ensure!(
+        output.status.success(),
+        "failed getting commit time: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Creates archives in a single format and with shared settings.
+///
+/// Archives are reproducible, meaning that the same input files always result in the exact same
+/// archive. Entries are sorted, owners are reset to root, permissions are normalized to
+/// `0o755` for executables and `0o644` for other files, and all timestamps are set to a fixed
+/// date.
+pub struct Archiver<'a> {
+    format: ArchiveFormat,
+    level: Option<u32>,
+    comment: &'a str,
+    wrap_dir: bool,
+    mtime: OffsetDateTime,
+}
+
+impl<'a> Archiver<'a> {
+    /// Prepare archives in the given format, compressed with the default level of the format if
+    /// none is given. The comment describes how the files were built, like the compiler version,
+    /// and is stored in all formats except [`ArchiveFormat::Raw`]. Tar archives keep it in a
+    /// global pax header, which is skipped when unpacking them.
+    pub fn new(format: ArchiveFormat, level: Option<u32>, comment: &'a str) -> Result<Self> {
+        format.check_level(level)?;
+
+        Ok(Self {
+            format,
+            level,
+            comment,
+            wrap_dir: false,
+            mtime: OffsetDateTime::UNIX_EPOCH,
+        })
+    }
+
+    /// Set the modification time of all files, which defaults to the Unix epoch.
+    #[must_use]
+    pub fn with_mtime(mut self, mtime: OffsetDateTime) -> Self {
+        self.mtime = mtime;
+        self
+    }
+
+    /// Place all files in a top-level directory that is named like the archive, instead of the
+    /// archive's root. Doesn't apply to the [`ArchiveFormat::Raw`] format.
+    #[must_use]
+    pub fn with_wrap_dir(mut self, wrap_dir: bool) -> Self {
+        self.wrap_dir = wrap_dir;
+        self
+    }
+
+    /// Package the files into an archive and return it, together with its file name. The name is
+    /// extended by the format's file extension.
+    pub fn pack(&self, files: &Entries<'_>, name: &str) -> Result<(String, Vec<u8>)> {
+        match self.format {
+            ArchiveFormat::Raw => {
+                let [(file, path)] = files else {
+                    bail!(
+                        "the raw format only supports a single file, but got {}",
+                        files.len()
+                    );
+                };
+
+                let name = match Utf8Path::new(path).extension() {
+                    Some(extension) => format!("{name}.{extension}"),
+                    None => name.to_owned(),
+                };
+
+                Ok((name, fs::read(file)?))
+            }
+            ArchiveFormat::Zip => Ok((format!("{name}.zip"), self.zip(files, name)?)),
+            format => {
+                let data = self.compress(format, &self.tar(files, name)?)?;
+                Ok((format!("{name}.{format}"), data))
+            }
+        }
+    }
+
+    /// Package separate debug information files, like `.debug` or `.pdb` files and `.dSYM`
+    /// bundles, into a tar archive that is published next to the regular one. It uses the same
+    /// compression as the regular archive, or `tar.gz` if that isn't a tar archive.
+    pub fn pack_debug(&self, files: &Entries<'_>, name: &str) -> Result<(String, Vec<u8>)> {
+        let format = match self.format {
+            ArchiveFormat::Zip | ArchiveFormat::Raw => ArchiveFormat::TarGz,
+            format => format,
+        };
+
+        let data = self.compress(format, &self.tar(files, name)?)?;
+
+        Ok((format!("{name}.{format}"), data))
+    }
+
+    fn tar(&self, files: &Entries<'_>, name: &str) -> Result<Vec<u8>> {
+        let mut builder = TarBuilder::new(Vec::new());
+
+        if !self.comment.is_empty() {
+            let record = pax_record("comment", self.comment);
+            let mut header = tar_header(
+                EntryType::XGlobalHeader,
+                0o644,
+                record.len() as u64,
+                self.mtime,
+            )?;
+            builder.append_data(&mut header, "pax_global_header", record.as_bytes())?;
+        }
+
+        for (file, path) in sorted(files) {
+            self.append_tar(&mut builder, file, &self.path(name, path))?;
+        }
+
+        Ok(builder.into_inner()?)
+    }
+
+    /// Add a file, or a directory with all its content, to the tar archive.
+    fn append_tar(
+        &self,
+        builder: &mut TarBuilder<Vec<u8>>,
+        file: &Utf8Path,
+        path: &str,
+    ) -> Result<()> {
+        if file.is_dir() {
+            let mut header = tar_header(EntryType::Directory, 0o755, 0, self.mtime)?;
+            builder.append_data(&mut header, path, io::empty())?;
+
+            let mut children = file
+                .read_dir_utf8()?
+                .map(|entry| Ok(entry?.into_path()))
+                .collect::<Result<Vec<Utf8PathBuf>>>()?;
+            children.sort();
+
+            for child in children {
+                let name = child.file_name().unwrap_or_default();
+                self.append_tar(builder, &child, &format!("{path}/{name}"))?;
+            }
+        } else {
+            let mut header = tar_header(
+                EntryType::Regular,
+                mode(file)?,
+                file.metadata()?.len(),
+                self.mtime,
+            )?;
+            builder.append_data(&mut header, path, File::open(file)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn compress(&self, format: ArchiveFormat, data: &[u8]) -> Result<Vec<u8>> {
+        let level = self
+            .level
+            .filter(|_| format == self.format)
+            .or_else(|| format.levels().map(|(_, default)| default))
+            .unwrap_or_default();
+
+        Ok(match format {
+            ArchiveFormat::TarGz => {
+                let mut encoder = GzBuilder::new()
+                    .mtime(0)
+                    .operating_system(255)
+                    .comment(self.comment)
+                    .write(Vec::new(), Compression::new(level));
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            ArchiveFormat::TarXz => {
+                let mut encoder = XzEncoder::new(Vec::new(), level);
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            ArchiveFormat::TarZst => zstd::encode_all(data, level.try_into()?)?,
+            ArchiveFormat::TarBz2 => {
+                let mut encoder = BzEncoder::new(Vec::new(), bzip2::Compression::new(level));
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            ArchiveFormat::Zip | ArchiveFormat::Raw => unreachable!("{format} isn't a tar format"),
+        })
+    }
+
+    /// Location of a file inside the archive, including the wrapping directory if enabled.
+    fn path(&self, name: &str, path: &str) -> String {
+        if self.wrap_dir {
+            format!("{name}/{path}")
+        } else {
+            path.to_owned()
+        }
+    }
+
+    fn zip(&self, files: &Entries<'_>, name: &str) -> Result<Vec<u8>> {
+        let writer = Cursor::new(Vec::new());
+        let mut writer = ZipWriter::new(writer);
+        writer.set_comment(self.comment);
+
+        // Zip timestamps can only express the years 1980 to 2107.
+        let mtime = DateTime::from_date_and_time(
+            self.mtime.year().try_into()?,
+            self.mtime.month().into(),
+            self.mtime.day(),
+            self.mtime.hour(),
+            self.mtime.minute(),
+            self.mtime.second(),
+        )
+        .unwrap_or_default();
+
+        let options = ZipFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(self.level.map(i32::try_from).transpose()?)
+            .last_modified_time(mtime);
+
+        for (file, path) in sorted(files) {
+            writer.start_file(self.path(name, path), options.unix_permissions(mode(file)?))?;
+
+            let mut file = File::open(file)?;
+            std::io::copy(&mut file, &mut writer)?;
+        }
+
+        Ok(writer.finish()?.into_inner())
+    }
+}
+
+fn sorted<'a>(files: &'a Entries<'a>) -> Vec<(&'a Utf8Path, &'a str)> {
+    let mut files = files.to_vec();
+    files.sort_by_key(|(_, path)| *path);
+    files
+}
+
+/// Normalized permissions of a file, depending on whether it's executable.
+pub fn mode(file: &Utf8Path) -> Result<u32> {
+    #[cfg(unix)]
+    let executable = {
+        use std::os::unix::fs::PermissionsExt;
+        file.metadata()?.permissions().mode() & 0o111 != 0
+    };
+    #[cfg(not(unix))]
+    let executable = file.extension() == Some("exe");
+
+    Ok(if executable { 0o755 } else { 0o644 })
+}
+
+/// Header of a tar entry that only depends on the given values, with root as owner and group.
+pub fn tar_header(
+    kind: EntryType,
+    mode: u32,
+    size: u64,
+    mtime: OffsetDateTime,
+) -> Result<TarHeader> {
     let mut header = TarHeader::new_gnu();
-    header.set_mode(0o755);
-    header.set_size(file.metadata()?.len());
-    header.set_path(name)?;
-    header.set_cksum();
+    header.set_entry_type(kind);
+    header.set_mode(mode);
+    header.set_size(size);
+    header.set_mtime(mtime.unix_timestamp().try_into().unwrap_or_default());
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_username("root")?;
+    header.set_groupname("root")?;
 
-    builder.append(&header, File::open(file)?)?;
+    Ok(header)
+}
 
-    let data = builder.into_inner()?.finish()?;
+/// Encode a key-value pair as pax extended header record, which is prefixed by its own length in
+/// bytes, including the length's digits.
+fn pax_record(key: &str, value: &str) -> String {
+    let base = key.len() + value.len() + 3;
+    let mut len = base;
+    while len != base + len.to_string().len() {
+        len = base + len.to_string().len();
+    }
 
-    Ok((archive_name, data))
+    format!("{len} {key}={value}\n")
 }
 
-pub fn zip(file: &Utf8Path, name: &str, target: &Triple) -> Result<(String, Vec<u8>)> {
-    let archive_name = format!("{name}-{target}.zip");
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use assert_fs::{prelude::*, TempDir};
+    use camino::Utf8PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn pack_formats() -> Result<()> {
+        let dir = TempDir::new()?;
+        let file = dir.child("demo");
+        file.write_binary(&[7; 1024])?;
+        let file = Utf8PathBuf::try_from(file.to_path_buf())?;
+        let files = [(file.as_path(), "demo")];
+
+        for (format, level) in [
+            (ArchiveFormat::TarGz, None),
+            (ArchiveFormat::TarXz, Some(9)),
+            (ArchiveFormat::TarZst, Some(3)),
+            (ArchiveFormat::TarBz2, None),
+        ] {
+            let (name, data) = Archiver::new(format, level, "test")?.pack(&files, "demo-x")?;
+            assert_eq!(format!("demo-x.{format}"), name);
+
+            let mut tar = Vec::new();
+            match format {
+                ArchiveFormat::TarGz => flate2::read::GzDecoder::new(&*data).read_to_end(&mut tar),
+                ArchiveFormat::TarXz => xz2::read::XzDecoder::new(&*data).read_to_end(&mut tar),
+                ArchiveFormat::TarZst => zstd::Decoder::new(&*data)?.read_to_end(&mut tar),
+                _ => bzip2::read::BzDecoder::new(&*data).read_to_end(&mut tar),
+            }?;
+
+            let mut archive = tar::Archive::new(&*tar);
+            let mut entries = archive.entries()?;
+            let mut header = entries.next().context("missing pax header")??;
+            let comment = header
+                .pax_extensions()?
+                .context("missing pax extensions")?
+                .find_map(|extension| extension.ok().filter(|ext| ext.key() == Ok("comment")))
+                .context("missing comment")?;
+            assert_eq!(b"test", comment.value_bytes());
+
+            let entry = entries.next().context("missing file")??;
+            assert_eq!("demo", entry.path()?.to_string_lossy());
+            assert_eq!(1024, entry.size());
+        }
+
+        assert_eq!("6 a=b\n", pax_record("a", "b"));
+        let record = pax_record("comment", &"x".repeat(88));
+        assert!(record.starts_with("101 "));
+        assert_eq!(101, record.len());
+
+        let (name, data) = Archiver::new(ArchiveFormat::Raw, None, "")?.pack(&files, "demo-x")?;
+        assert_eq!("demo-x", name);
+        assert_eq!(vec![7; 1024], data);
+
+        let files = [(file.as_path(), "demo.exe")];
+        let (name, _) = Archiver::new(ArchiveFormat::Raw, None, "")?.pack(&files, "demo-x")?;
+        assert_eq!("demo-x.exe", name);
+
+        let (name, _) = Archiver::new(ArchiveFormat::Zip, Some(1), "")?.pack(&files, "demo-x")?;
+        assert_eq!("demo-x.zip", name);
+
+        let (_, data) = Archiver::new(ArchiveFormat::TarGz, None, "")?
+            .with_wrap_dir(true)
+            .pack(&files, "demo-x")?;
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(&*data));
+        let entry = archive.entries()?.next().unwrap()?;
+        assert_eq!("demo-x/demo.exe", entry.path()?.to_string_lossy());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reproducible() -> Result<()> {
+        let mtime = OffsetDateTime::from_unix_timestamp(1_700_000_000)?;
+
+        let build = |format| -> Result<Vec<u8>> {
+            // Fresh copies in a new location, with a different modification time on every run.
+            let dir = TempDir::new()?;
+            dir.child("b/LICENSE").write_str("MIT")?;
+            dir.child("a/demo").write_binary(&[1; 512])?;
+            dir.child("c/demo.debug/info").write_str("debug")?;
+
+            let root = Utf8PathBuf::try_from(dir.to_path_buf())?;
+            let (b, a, c) = (
+                root.join("b/LICENSE"),
+                root.join("a/demo"),
+                root.join("c/demo.debug"),
+            );
+            let archiver = Archiver::new(format, None, "rustc 1.76.0")?
+                .with_wrap_dir(true)
+                .with_mtime(mtime);
 
-    let writer = Cursor::new(Vec::new());
-    let mut writer = ZipWriter::new(writer);
+            let (_, mut data) = archiver.pack(&[(&b, "LICENSE"), (&a, "demo")], "demo")?;
+            data.extend(archiver.pack_debug(&[(&c, "demo.debug")], "demo-debug")?.1);
 
-    let options = ZipFileOptions::default()
-        .compression_method(CompressionMethod::Deflated)
-        .unix_permissions(0o755);
+            Ok(data)
+        };
 
-    writer.start_file(name, options)?;
+        for format in [
+            ArchiveFormat::TarGz,
+            ArchiveFormat::TarXz,
+            ArchiveFormat::TarZst,
+            ArchiveFormat::TarBz2,
+            ArchiveFormat::Zip,
+        ] {
+            let first = build(format)?;
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            assert!(
+                first == build(format)?,
+                "{format} archive isn't reproducible"
+            );
+        }
 
-    let mut file = File::open(file)?;
-    std::io::copy(&mut file, &mut writer)?;
+        // The gzip header has neither a timestamp nor a specific operating system.
+        let (_, data) = Archiver::new(ArchiveFormat::TarGz, None, "")?.pack_debug(&[], "empty")?;
+        assert_eq!([0, 0, 0, 0], data[4..8]);
+        assert_eq!(255, data[9]);
 
-    let data = writer.finish()?.into_inner();
+        Ok(())
+    }
 
-    Ok((archive_name, data))
+    #[test]
+    fn check_level() {
+        assert!(ArchiveFormat::TarZst.check_level(Some(22)).is_ok());
+        assert!(ArchiveFormat::TarZst.check_level(Some(0)).is_err());
+        assert!(ArchiveFormat::TarGz.check_level(Some(10)).is_err());
+        assert!(ArchiveFormat::Raw.check_level(Some(1)).is_err());
+        assert!(ArchiveFormat::Raw.check_level(None).is_ok());
+    }
 }
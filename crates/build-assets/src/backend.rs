@@ -0,0 +1,189 @@
+use std::process::{Command, Stdio};
+
+use anyhow::{ensure, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+/// Tool that drives the compilation of the binaries.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Plain `cargo build`, with cross toolchains installed through APT.
+    #[default]
+    Cargo,
+    /// [`cargo zigbuild`](https://github.com/rust-cross/cargo-zigbuild), using Zig as linker
+    /// for any target and allowing to pin the glibc version.
+    Zigbuild,
+    /// [`cross build`](https://github.com/cross-rs/cross), compiling inside a Docker container
+    /// that contains the full toolchain for the target.
+    Cross,
+}
+
+impl Backend {
+    /// Whether the backend relies on C toolchains from the host system, that have to be set up
+    /// before the build.
+    #[must_use]
+    pub fn uses_host_toolchain(self) -> bool {
+        self == Self::Cargo
+    }
+
+    /// Whether the backend can link against a specific glibc version.
+    #[must_use]
+    pub fn supports_glibc_version(self) -> bool {
+        self == Self::Zigbuild
+    }
+
+    /// Value for the `--target` argument. `cargo zigbuild` takes the glibc version to link
+    /// against as suffix of the target triple, but places the artifacts in the directory of the
+    /// plain triple.
+    #[must_use]
+    pub fn target_arg(self, target: &str, glibc_version: Option<&str>) -> String {
+        match glibc_version {
+            Some(version) if self == Self::Zigbuild => format!("{target}.{version}"),
+            _ => target.to_owned(),
+        }
+    }
+
+    /// Translate a path reported by the build into the location on the host. `cross` mounts the
+    /// target directory as `/target` into its container, so cargo only knows that location.
+    #[must_use]
+    pub fn host_path(self, path: Utf8PathBuf, target_dir: &Utf8Path) -> Utf8PathBuf {
+        match path.strip_prefix("/target") {
+            Ok(relative) if self == Self::Cross => target_dir.join(relative),
+            _ => path,
+        }
+    }
+
+    /// Install the backend's tools, unless they're already available.
+    pub fn install(self) -> Result<()> {
+        match self {
+            Self::Cargo => {}
+            Self::Zigbuild => {
+                if !is_available("zig", &["version"]) {
+                    run(
+                        Command::new("pip3").args(["install", "ziglang"]),
+                        "failed installing Zig",
+                    )?;
+                }
+
+                if !is_available("cargo", &["zigbuild", "--version"]) {
+                    cargo_install("cargo-zigbuild")?;
+                }
+            }
+            Self::Cross => {
+                if !is_available("cross", &["--version"]) {
+                    cargo_install("cross")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create the base command for compiling, that the remaining build arguments are appended to.
+    #[must_use]
+    pub fn command(self) -> Command {
+        match self {
+            Self::Cargo => {
+                let mut cmd = Command::new("cargo");
+                cmd.arg("build");
+                cmd
+            }
+            Self::Zigbuild => {
+                let mut cmd = Command::new("cargo");
+                cmd.arg("zigbuild");
+                cmd
+            }
+            Self::Cross => {
+                let mut cmd = Command::new("cross");
+                cmd.arg("build");
+                cmd
+            }
+        }
+    }
+}
+
+fn is_available(program: &str, args: &[&str]) -> bool {
+    Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn cargo_install(krate: &str) -> Result<()> {
+    run(
+        Command::new("cargo").args(["install", "--locked", krate]),
+        &format!("failed installing `{krate}`"),
+    )
+}
+
+fn run(cmd: &mut Command, message: &str) -> Result<()> {
+    let output = cmd.output()?;
+    ensure!(
+        output.status.success(),
+        "{message}: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command() {
+        let program = |backend: Backend| {
+            let cmd = backend.command();
+            let mut parts = vec![cmd.get_program().to_string_lossy().into_owned()];
+            parts.extend(cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()));
+            parts.join(" ")
+        };
+
+        assert_eq!("cargo build", program(Backend::Cargo));
+        assert_eq!("cargo zigbuild", program(Backend::Zigbuild));
+        assert_eq!("cross build", program(Backend::Cross));
+    }
+
+    #[test]
+    fn zigbuild_glibc_version() {
+        let target = "aarch64-unknown-linux-gnu";
+        assert_eq!(
+            "aarch64-unknown-linux-gnu.2.17",
+            Backend::Zigbuild.target_arg(target, Some("2.17"))
+        );
+        assert_eq!(target, Backend::Zigbuild.target_arg(target, None));
+        assert_eq!(target, Backend::Cargo.target_arg(target, Some("2.17")));
+
+        // The artifacts land in the directory of the plain triple, as reported by cargo.
+        let path = Utf8PathBuf::from("/work/target/aarch64-unknown-linux-gnu/release/demo");
+        assert_eq!(
+            path,
+            Backend::Zigbuild.host_path(path.clone(), "/work/target".into())
+        );
+    }
+
+    #[test]
+    fn cross_paths() {
+        let target_dir = Utf8Path::new("/work/target");
+        let path = |path: &str| Backend::Cross.host_path(path.into(), target_dir);
+
+        assert_eq!(
+            "/work/target/aarch64-unknown-linux-gnu/release/demo",
+            path("/target/aarch64-unknown-linux-gnu/release/demo")
+        );
+        assert_eq!(
+            "/work/target/aarch64-unknown-linux-gnu/release/build/demo-0123/out",
+            path("/target/aarch64-unknown-linux-gnu/release/build/demo-0123/out")
+        );
+        assert_eq!("/project/README.md", path("/project/README.md"));
+        assert_eq!("/targets/demo", path("/targets/demo"));
+        assert_eq!(
+            "/target/release/demo",
+            Backend::Cargo.host_path("/target/release/demo".into(), target_dir)
+        );
+    }
+}
@@ -1,78 +1,280 @@
 use std::{
+    collections::HashMap,
     env,
     io::BufReader,
     process::{Command, Stdio},
 };
 
-use anyhow::{ensure, Context, Result};
-use camino::Utf8PathBuf;
+use anyhow::{ensure, Result};
+use camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::Message;
 use heck::{ToShoutySnakeCase, ToSnakeCase};
 use target_lexicon::Triple;
 
+use crate::{
+    backend::Backend,
+    debuginfo::DebugFormat,
+    features::Features,
+    targets::{EnvVar, TargetConfig},
+    toolchain::Toolchain,
+};
+
 pub struct CargoBuilder<'a> {
     cmd: Command,
-    bin: &'a str,
+    backend: Backend,
+    target_dir: Option<Utf8PathBuf>,
+    bins: &'a [String],
     target: String,
+    glibc_version: Option<&'a str>,
+    profile: &'a str,
+    rustflags: &'static [&'static str],
+}
+
+/// Compiled executable, as reported by cargo.
+pub struct Binary {
+    /// Name of the binary target.
+    pub name: String,
+    /// Location of the executable in the target directory.
+    pub path: Utf8PathBuf,
+    /// Separate files that contain the debug information for the executable.
+    pub debug: Vec<Utf8PathBuf>,
+    /// Output directory of the package's build script, if it has one.
+    pub out_dir: Option<Utf8PathBuf>,
+}
+
+impl Binary {
+    /// File name of the executable, including the platform-specific extension.
+    #[must_use]
+    pub fn file_name(&self) -> &str {
+        self.path.file_name().unwrap_or(&self.name)
+    }
 }
 
 impl<'a> CargoBuilder<'a> {
+    /// Prepare the build of the given binaries with the selected backend. If no binaries are
+    /// given, all binaries of the package are built.
     #[must_use]
-    pub fn new(bin: &'a str, target: &'a Triple) -> Self {
+    pub fn new(backend: Backend, bins: &'a [String], target: &'a Triple) -> Self {
+        let mut cmd = backend.command();
+        cmd.args(["--message-format", "json-render-diagnostics"])
+            .stdout(Stdio::piped());
+
+        if *target != Triple::host() {
+            cmd.env("PKG_CONFIG_ALLOW_CROSS", "1");
+        }
+
         let target = target.to_string();
 
-        let rustflags = match env::var_os("RUSTFLAGS") {
-            Some(flags) => format!("{} -C strip=symbols", flags.to_string_lossy()),
-            None => "-C strip=symbols".to_owned(),
+        if bins.is_empty() {
+            cmd.arg("--bins");
+        }
+
+        for bin in bins {
+            cmd.args(["--bin", bin]);
+        }
+
+        Self {
+            cmd,
+            backend,
+            target_dir: None,
+            bins,
+            target,
+            glibc_version: None,
+            profile: "release",
+            rustflags: &["-C", "strip=symbols"],
+        }
+    }
+
+    /// Use the C toolchain and environment from the given target config.
+    #[must_use]
+    pub fn with_target_config(mut self, config: Option<&TargetConfig>) -> Self {
+        let Some(config) = config else {
+            return self;
         };
 
-        let mut cmd = Command::new("cargo");
-        cmd.args(["build", "--release"])
-            .args(["--message-format", "json-render-diagnostics"])
-            .args(["--bin", bin])
-            .args(["--target", &target])
-            .env("RUSTFLAGS", rustflags)
-            .stdout(Stdio::piped());
+        if let Some(cc) = &config.cc {
+            self.cmd
+                .env(format!("CC_{}", self.target.to_snake_case()), cc);
+        }
+
+        if let Some(cxx) = &config.cxx {
+            self.cmd
+                .env(format!("CXX_{}", self.target.to_snake_case()), cxx);
+        }
 
-        Self { cmd, bin, target }
+        if let Some(linker) = &config.linker {
+            self.cmd.env(
+                format!("CARGO_TARGET_{}_LINKER", self.target.to_shouty_snake_case()),
+                linker,
+            );
+        }
+
+        if let Some(ar) = &config.ar {
+            self.cmd
+                .env(format!("AR_{}", self.target.to_snake_case()), ar);
+        }
+
+        if let Some(libdir) = &config.pkg_config_libdir {
+            self.cmd.env(
+                format!("PKG_CONFIG_LIBDIR_{}", self.target.to_snake_case()),
+                libdir,
+            );
+        }
+
+        if let Some(sysroot) = &config.sysroot {
+            self.cmd.env(
+                format!("PKG_CONFIG_SYSROOT_DIR_{}", self.target.to_snake_case()),
+                sysroot,
+            );
+        }
+
+        self.cmd.envs(&config.env);
+        self
     }
 
+    /// Set additional environment variables, overriding any previously set ones.
     #[must_use]
-    pub fn with_compiler(mut self, compiler: Option<&str>) -> Self {
-        if let Some(compiler) = compiler {
-            self.cmd.envs([
-                (format!("CC_{}", self.target.to_snake_case()), compiler),
-                (
-                    format!("CARGO_TARGET_{}_LINKER", self.target.to_shouty_snake_case()),
-                    compiler,
-                ),
-            ]);
+    pub fn with_env(mut self, vars: &[EnvVar]) -> Self {
+        self.cmd.envs(vars.iter().map(|var| (&var.key, &var.value)));
+        self
+    }
+
+    #[must_use]
+    pub fn with_toolchain(mut self, toolchain: &Toolchain) -> Self {
+        self.cmd.env("RUSTUP_TOOLCHAIN", toolchain.to_string());
+        self
+    }
+
+    /// Link against the given glibc version, instead of the one from the build system. Only
+    /// supported by the [`Backend::Zigbuild`] backend.
+    #[must_use]
+    pub fn with_glibc_version(mut self, version: Option<&'a str>) -> Self {
+        self.glibc_version = version;
+        self
+    }
+
+    /// Use a custom profile instead of the default `release` profile.
+    #[must_use]
+    pub fn with_profile(mut self, profile: Option<&'a str>) -> Self {
+        if let Some(profile) = profile {
+            self.profile = profile;
         }
 
         self
     }
 
+    /// Compile the standard library from source, which requires a nightly toolchain and the
+    /// `rust-src` component.
     #[must_use]
-    pub fn with_features(mut self, features: &[String]) -> Self {
-        if !features.is_empty() {
-            self.cmd.arg("--features").arg(features.join(","));
+    pub fn with_build_std(mut self, build_std: bool) -> Self {
+        if build_std {
+            self.cmd.args(["-Z", "build-std=std,panic_abort"]);
         }
 
         self
     }
 
-    pub fn run(mut self) -> Result<Utf8PathBuf> {
-        let mut child = self.cmd.spawn()?;
+    /// Build with full debug information that is stored separately from the executable, instead
+    /// of stripping all symbols.
+    #[must_use]
+    pub fn with_debug_symbols(mut self, format: Option<DebugFormat>) -> Self {
+        if let Some(format) = format {
+            self.rustflags = format.rustflags();
+        }
+
+        self
+    }
+
+    #[must_use]
+    pub fn with_package(mut self, package: Option<&str>) -> Self {
+        if let Some(package) = package {
+            self.cmd.args(["--package", package]);
+        }
+
+        self
+    }
+
+    #[must_use]
+    pub fn with_manifest_path(mut self, path: Option<&Utf8Path>) -> Self {
+        if let Some(path) = path {
+            self.cmd.arg("--manifest-path").arg(path);
+        }
+
+        self
+    }
+
+    /// Place all build artifacts in the given directory. It's passed explicitly even if it's the
+    /// workspace's default, as paths reported from inside a `cross` container are relative to it.
+    #[must_use]
+    pub fn with_target_dir(mut self, path: &Utf8Path) -> Self {
+        self.cmd.arg("--target-dir").arg(path);
+        self.target_dir = Some(path.to_owned());
+        self
+    }
+
+    #[must_use]
+    pub fn with_features(mut self, features: &Features) -> Self {
+        if !features.features.is_empty() {
+            self.cmd.arg("--features").arg(features.features.join(","));
+        }
+
+        if features.no_default_features {
+            self.cmd.arg("--no-default-features");
+        }
+
+        if features.all_features {
+            self.cmd.arg("--all-features");
+        }
+
+        self
+    }
+
+    pub fn run(mut self) -> Result<Vec<Binary>> {
+        let rustflags = match env::var_os("RUSTFLAGS") {
+            Some(flags) => format!("{} {}", flags.to_string_lossy(), self.rustflags.join(" ")),
+            None => self.rustflags.join(" "),
+        };
+
+        let target = self.backend.target_arg(&self.target, self.glibc_version);
+        let host_path = |path: Utf8PathBuf| match &self.target_dir {
+            Some(target_dir) => self.backend.host_path(path, target_dir),
+            None => path,
+        };
+
+        let mut child = self
+            .cmd
+            .args(["--target", &target])
+            .args(["--profile", self.profile])
+            .env("RUSTFLAGS", rustflags)
+            .spawn()?;
         let reader = BufReader::new(child.stdout.take().unwrap());
-        let mut binary = None;
+        let mut binaries = Vec::<Binary>::new();
+        let mut out_dirs = HashMap::new();
 
         for msg in Message::parse_stream(reader) {
             match msg? {
+                Message::BuildScriptExecuted(script) => {
+                    out_dirs.insert(script.package_id, host_path(script.out_dir));
+                }
                 Message::CompilerArtifact(artifact) => {
-                    if let Some(bin) = artifact.executable.filter(|_| {
-                        artifact.target.name == self.bin && artifact.target.kind == ["bin"]
+                    if let Some(path) = artifact.executable.filter(|_| {
+                        artifact.target.kind == ["bin"]
+                            && (self.bins.is_empty() || self.bins.contains(&artifact.target.name))
+                            && binaries.iter().all(|bin| bin.name != artifact.target.name)
                     }) {
-                        binary = binary.or(Some(bin));
+                        let debug = artifact
+                            .filenames
+                            .into_iter()
+                            .filter(|file| file != &path)
+                            .map(host_path)
+                            .collect();
+
+                        binaries.push(Binary {
+                            name: artifact.target.name,
+                            path: host_path(path),
+                            debug,
+                            out_dir: out_dirs.get(&artifact.package_id).cloned(),
+                        });
                     }
                 }
                 Message::CompilerMessage(message) => println!("{message}"),
@@ -84,6 +286,59 @@ impl<'a> CargoBuilder<'a> {
             }
         }
 
-        binary.context("failed finding binary after compilation")
+        for bin in self.bins {
+            ensure!(
+                binaries.iter().any(|binary| &binary.name == bin),
+                "failed finding binary `{bin}` after compilation"
+            );
+        }
+        ensure!(
+            !binaries.is_empty(),
+            "failed finding any binary after compilation"
+        );
+
+        Ok(binaries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+    use crate::targets::TargetTable;
+
+    #[test]
+    fn target_config_env() -> Result<()> {
+        let table = TargetTable::load(None)?;
+        let target = "aarch64-unknown-linux-gnu".parse()?;
+        let builder =
+            CargoBuilder::new(Backend::Cargo, &[], &target).with_target_config(table.find(&target));
+
+        let envs = builder
+            .cmd
+            .get_envs()
+            .filter_map(|(key, value)| Some((key.to_str()?, value.and_then(OsStr::to_str)?)))
+            .filter(|(key, _)| *key != "PKG_CONFIG_ALLOW_CROSS")
+            .collect::<HashMap<_, _>>();
+
+        assert_eq!(
+            HashMap::from([
+                ("CC_aarch64_unknown_linux_gnu", "aarch64-linux-gnu-gcc"),
+                ("CXX_aarch64_unknown_linux_gnu", "aarch64-linux-gnu-g++"),
+                ("AR_aarch64_unknown_linux_gnu", "aarch64-linux-gnu-ar"),
+                (
+                    "CARGO_TARGET_AARCH64_UNKNOWN_LINUX_GNU_LINKER",
+                    "aarch64-linux-gnu-gcc"
+                ),
+                (
+                    "PKG_CONFIG_LIBDIR_aarch64_unknown_linux_gnu",
+                    "/usr/lib/aarch64-linux-gnu/pkgconfig:/usr/share/pkgconfig"
+                ),
+            ]),
+            envs
+        );
+
+        Ok(())
     }
 }
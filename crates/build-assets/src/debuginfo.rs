@@ -0,0 +1,140 @@
+use std::process::Command;
+
+use anyhow::{bail, ensure, Result};
+use camino::Utf8PathBuf;
+use target_lexicon::{BinaryFormat, Environment, Triple};
+
+use crate::{cargo::Binary, targets::TargetConfig};
+
+/// The way debug information is stored separately from an executable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugFormat {
+    /// Extracted from the executable into a `.debug` file with `objcopy` after compilation.
+    Objcopy,
+    /// Bundled into a `.dSYM` directory by the Apple linker tools.
+    Dsym,
+    /// Written into a `.pdb` file by the MSVC linker.
+    Pdb,
+}
+
+impl DebugFormat {
+    pub fn for_target(target: &Triple) -> Result<Self> {
+        Ok(match (target.binary_format, target.environment) {
+            (BinaryFormat::Macho, _) => Self::Dsym,
+            (BinaryFormat::Coff, Environment::Msvc) => Self::Pdb,
+            (BinaryFormat::Elf | BinaryFormat::Coff, _) => Self::Objcopy,
+            _ => bail!("separate debug symbols are not supported for target `{target}`"),
+        })
+    }
+
+    /// Compiler flags to build with full debug information, while keeping the executable itself
+    /// stripped, if possible.
+    #[must_use]
+    pub fn rustflags(self) -> &'static [&'static str] {
+        match self {
+            // Symbols are stripped by objcopy afterwards.
+            Self::Objcopy => &["-C", "debuginfo=2"],
+            // Stripping happens after the dSYM bundle was created.
+            Self::Dsym => &[
+                "-C",
+                "debuginfo=2",
+                "-C",
+                "split-debuginfo=packed",
+                "-C",
+                "strip=symbols",
+            ],
+            // Stripping would disable the PDB file, but the executable doesn't contain any debug
+            // information anyway.
+            Self::Pdb => &["-C", "debuginfo=2", "-C", "split-debuginfo=packed"],
+        }
+    }
+}
+
+/// Move the debug information of an ELF or MinGW executable into a separate `.debug` file, and
+/// strip the executable afterwards. The file is recorded as part of the binary's debug files.
+pub fn split(binary: &mut Binary, objcopy: &str) -> Result<()> {
+    let debug = Utf8PathBuf::from(format!("{}.debug", binary.path));
+
+    let output = Command::new(objcopy)
+        .arg("--only-keep-debug")
+        .args([&binary.path, &debug])
+        .output()?;
+    ensure!(
+        output.status.success(),
+        "failed extracting debug information: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = Command::new(objcopy)
+        .arg("--strip-all")
+        .arg(format!("--add-gnu-debuglink={debug}"))
+        .arg(&binary.path)
+        .output()?;
+    ensure!(
+        output.status.success(),
+        "failed stripping executable: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    binary.debug.push(debug);
+
+    Ok(())
+}
+
+/// The `objcopy` configured for the target. Without one, the host's `objcopy` is used for targets
+/// of the host architecture, and `llvm-objcopy` for all others, as it handles any architecture.
+#[must_use]
+pub fn objcopy(config: Option<&TargetConfig>, target: &Triple) -> String {
+    if let Some(objcopy) = config.and_then(|config| config.objcopy.as_ref()) {
+        objcopy.clone()
+    } else if target.architecture == Triple::host().architecture {
+        "objcopy".to_owned()
+    } else {
+        "llvm-objcopy".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use target_lexicon::{Aarch64Architecture, Architecture};
+
+    use super::*;
+    use crate::targets::TargetTable;
+
+    #[test]
+    fn debug_format() -> Result<()> {
+        let format = |target: &str| DebugFormat::for_target(&target.parse()?);
+
+        assert_eq!(DebugFormat::Objcopy, format("x86_64-unknown-linux-gnu")?);
+        assert_eq!(DebugFormat::Objcopy, format("x86_64-pc-windows-gnu")?);
+        assert_eq!(DebugFormat::Dsym, format("aarch64-apple-darwin")?);
+        assert_eq!(DebugFormat::Pdb, format("x86_64-pc-windows-msvc")?);
+        assert!(format("wasm32-unknown-unknown").is_err());
+
+        let table = TargetTable::load(None)?;
+        let objcopy = |target: &str| {
+            let target = target.parse()?;
+            Ok::<_, anyhow::Error>(objcopy(table.find(&target), &target))
+        };
+
+        assert_eq!("objcopy", objcopy("x86_64-unknown-linux-musl")?);
+        assert_eq!("objcopy", objcopy("x86_64-unknown-linux-gnu")?);
+        assert_eq!(
+            "aarch64-linux-gnu-objcopy",
+            objcopy("aarch64-unknown-linux-gnu")?
+        );
+        assert_eq!("llvm-objcopy", objcopy("aarch64-unknown-linux-musl")?);
+
+        // Backends that bring their own toolchain don't use the target table.
+        let host = Triple::host();
+        let other = if host.architecture == Architecture::Aarch64(Aarch64Architecture::Aarch64) {
+            "x86_64-unknown-linux-gnu"
+        } else {
+            "aarch64-unknown-linux-gnu"
+        };
+        assert_eq!("objcopy", super::objcopy(None, &host));
+        assert_eq!("llvm-objcopy", super::objcopy(None, &other.parse()?));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,538 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display, Write as _},
+    fs::File,
+    io::{self, Read, Write},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Result};
+use camino::Utf8Path;
+use cargo_metadata::{semver::Version, Package};
+use flate2::{Compression, GzBuilder};
+use tar::{Builder as TarBuilder, EntryType};
+use target_lexicon::{Environment, Triple};
+use time::OffsetDateTime;
+
+use crate::{
+    archive::{self, Entries},
+    include::Include,
+};
+
+/// Native package format of a Linux distribution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackageKind {
+    /// Debian package, for Debian, Ubuntu and derivatives.
+    Deb,
+    /// RPM package, for Fedora, RHEL, openSUSE and derivatives.
+    Rpm,
+}
+
+/// Metadata of a distribution package, mostly taken from the cargo manifest.
+pub struct PackageInfo<'a> {
+    pub name: &'a str,
+    pub version: &'a Version,
+    pub description: Option<&'a str>,
+    pub license: Option<&'a str>,
+    pub authors: &'a [String],
+    pub homepage: Option<&'a str>,
+    /// Other packages that must be installed, in the syntax of the package format.
+    pub depends: &'a [String],
+    /// Modification time of all installed files.
+    pub mtime: OffsetDateTime,
+}
+
+impl<'a> PackageInfo<'a> {
+    /// Describe the package, named after the binary, with the metadata of the cargo package that
+    /// contains it.
+    #[must_use]
+    pub fn new(name: &'a str, package: &'a Package, mtime: OffsetDateTime) -> Self {
+        Self {
+            name,
+            version: &package.version,
+            description: package.description.as_deref(),
+            license: package.license.as_deref(),
+            authors: &package.authors,
+            homepage: package
+                .homepage
+                .as_deref()
+                .or(package.repository.as_deref()),
+            depends: &[],
+            mtime,
+        }
+    }
+
+    #[must_use]
+    pub fn with_depends(mut self, depends: &'a [String]) -> Self {
+        self.depends = depends;
+        self
+    }
+
+    /// Version in a form that the package format sorts correctly, where pre-releases like
+    /// `1.0.0-rc.1` come before the final release. Hyphens are valid within a deb version, as
+    /// long as it has a revision, but not in an rpm version.
+    fn version(&self, kind: PackageKind) -> String {
+        let Version {
+            major,
+            minor,
+            patch,
+            pre,
+            build,
+            ..
+        } = self.version;
+        let sanitize = |part: &str| match kind {
+            PackageKind::Deb => part.to_owned(),
+            PackageKind::Rpm => part.replace('-', "."),
+        };
+
+        let mut version = format!("{major}.{minor}.{patch}");
+        if !pre.is_empty() {
+            version.push('~');
+            version.push_str(&sanitize(pre));
+        }
+        if !build.is_empty() {
+            version.push('+');
+            version.push_str(&sanitize(build));
+        }
+
+        version
+    }
+
+    fn summary(&self) -> &str {
+        self.description
+            .and_then(|desc| desc.lines().next())
+            .unwrap_or(self.name)
+    }
+}
+
+impl FromStr for PackageKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "deb" => Self::Deb,
+            "rpm" => Self::Rpm,
+            _ => bail!("unknown package format `{s}`, expected `deb` or `rpm`"),
+        })
+    }
+}
+
+impl Display for PackageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Deb => "deb",
+            Self::Rpm => "rpm",
+        })
+    }
+}
+
+/// Extra file to install with the packages, in the form `<glob>:<dir>`. The glob follows the
+/// rules of [`Include`], while the directory must be absolute and is stored relative to the root
+/// of the target system.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackageFile(pub Include);
+
+impl FromStr for PackageFile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, dir) = s
+            .split_once(':')
+            .with_context(|| format!("missing install directory in `{s}`"))?;
+        let dir = dir
+            .strip_prefix('/')
+            .with_context(|| format!("install directory in `{s}` must be absolute"))?;
+
+        Ok(Self(format!("{pattern}:{dir}").parse()?))
+    }
+}
+
+impl Display for PackageFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let include = Include {
+            dest: None,
+            ..self.0.clone()
+        };
+        write!(
+            f,
+            "{include}:/{}",
+            self.0.dest.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+impl PackageKind {
+    /// Name of the target's architecture in this package format, failing if it has none.
+    pub fn arch(self, target: &Triple) -> Result<&'static str> {
+        match self {
+            Self::Deb => deb_arch(target),
+            Self::Rpm => rpm_arch(target),
+        }
+    }
+
+    /// Build the package for the target, with files given as pairs of the source location and
+    /// the absolute install path. Returns the file name together with the package content.
+    pub fn build(
+        self,
+        info: &PackageInfo<'_>,
+        files: &Entries<'_>,
+        target: &Triple,
+    ) -> Result<(String, Vec<u8>)> {
+        match self {
+            Self::Deb => {
+                let arch = self.arch(target)?;
+                let name = info.name.to_lowercase().replace('_', "-");
+                let data = deb(info, &name, files, arch)?;
+
+                Ok((format!("{name}_{}-1_{arch}.deb", info.version(self)), data))
+            }
+            Self::Rpm => {
+                let arch = self.arch(target)?;
+                let data = rpm(info, files, arch)?;
+
+                Ok((
+                    format!("{}-{}-1.{arch}.rpm", info.name, info.version(self)),
+                    data,
+                ))
+            }
+        }
+    }
+}
+
+fn deb_arch(target: &Triple) -> Result<&'static str> {
+    let arch = target.architecture.to_string();
+
+    Ok(match arch.as_str() {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "i386" | "i586" | "i686" => "i386",
+        _ if arch.starts_with("arm") && is_hard_float(target) => "armhf",
+        _ if arch.starts_with("arm") => "armel",
+        "riscv64gc" => "riscv64",
+        "powerpc64le" => "ppc64el",
+        "s390x" => "s390x",
+        "mips64el" => "mips64el",
+        "mipsel" => "mipsel",
+        _ => bail!("no Debian architecture known for target `{target}`"),
+    })
+}
+
+fn rpm_arch(target: &Triple) -> Result<&'static str> {
+    let arch = target.architecture.to_string();
+
+    Ok(match arch.as_str() {
+        "x86_64" => "x86_64",
+        "aarch64" => "aarch64",
+        "i586" => "i586",
+        "i686" => "i686",
+        "armv7" if is_hard_float(target) => "armv7hl",
+        "riscv64gc" => "riscv64",
+        "powerpc64le" => "ppc64le",
+        "s390x" => "s390x",
+        _ => bail!("no RPM architecture known for target `{target}`"),
+    })
+}
+
+fn is_hard_float(target: &Triple) -> bool {
+    matches!(
+        target.environment,
+        Environment::Gnueabihf | Environment::Musleabihf
+    )
+}
+
+/// Create a Debian package, which is an `ar` archive with the package metadata and the installed
+/// files as separate tar archives.
+fn deb(info: &PackageInfo<'_>, name: &str, files: &Entries<'_>, arch: &str) -> Result<Vec<u8>> {
+    let mut installed_size = 0;
+    let mut dirs = BTreeSet::new();
+    let mut entries = Vec::new();
+
+    for (file, path) in files {
+        let path = path
+            .strip_prefix('/')
+            .with_context(|| format!("install path `{path}` must be absolute"))?;
+
+        dirs.extend(
+            Utf8Path::new(path)
+                .ancestors()
+                .skip(1)
+                .filter(|dir| !dir.as_str().is_empty())
+                .map(|dir| format!("./{dir}/")),
+        );
+
+        installed_size += file.metadata()?.len().div_ceil(1024);
+        entries.push((format!("./{path}"), TarEntry::File(file)));
+    }
+
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let entries = [("./".to_owned(), TarEntry::Dir)]
+        .into_iter()
+        .chain(dirs.into_iter().map(|dir| (dir, TarEntry::Dir)))
+        .chain(entries)
+        .collect::<Vec<_>>();
+
+    let mut control = String::new();
+    writeln!(control, "Package: {name}")?;
+    writeln!(control, "Version: {}-1", info.version(PackageKind::Deb))?;
+    writeln!(control, "Architecture: {arch}")?;
+    writeln!(
+        control,
+        "Maintainer: {}",
+        info.authors.first().map_or("unknown", String::as_str)
+    )?;
+    writeln!(control, "Installed-Size: {installed_size}")?;
+    if !info.depends.is_empty() {
+        writeln!(control, "Depends: {}", info.depends.join(", "))?;
+    }
+    writeln!(control, "Section: utils")?;
+    writeln!(control, "Priority: optional")?;
+    if let Some(homepage) = info.homepage {
+        writeln!(control, "Homepage: {homepage}")?;
+    }
+    writeln!(control, "Description: {}", info.summary())?;
+    for line in info
+        .description
+        .into_iter()
+        .flat_map(str::lines)
+        .skip(1)
+        .skip_while(|line| line.trim().is_empty())
+    {
+        match line.trim() {
+            "" => writeln!(control, " .")?,
+            line => writeln!(control, " {line}")?,
+        }
+    }
+
+    let control = tar_gz(
+        &[
+            ("./".to_owned(), TarEntry::Dir),
+            ("./control".to_owned(), TarEntry::Data(control.as_bytes())),
+        ],
+        info.mtime,
+    )?;
+    let data = tar_gz(&entries, info.mtime)?;
+
+    let mut deb = b"!<arch>\n".to_vec();
+
+    for (name, content) in [
+        ("debian-binary", b"2.0\n".as_slice()),
+        ("control.tar.gz", &control),
+        ("data.tar.gz", &data),
+    ] {
+        writeln!(
+            deb,
+            "{name:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`",
+            info.mtime.unix_timestamp(),
+            0,
+            0,
+            "100644",
+            content.len()
+        )?;
+        deb.extend_from_slice(content);
+
+        if content.len() % 2 == 1 {
+            deb.push(b'\n');
+        }
+    }
+
+    Ok(deb)
+}
+
+enum TarEntry<'a> {
+    Dir,
+    File(&'a Utf8Path),
+    Data(&'a [u8]),
+}
+
+fn tar_gz(entries: &[(String, TarEntry<'_>)], mtime: OffsetDateTime) -> Result<Vec<u8>> {
+    let mut builder = TarBuilder::new(Vec::new());
+
+    for (path, entry) in entries {
+        let (kind, mode, size, reader): (_, _, _, Box<dyn Read>) = match entry {
+            TarEntry::Dir => (EntryType::Directory, 0o755, 0, Box::new(io::empty())),
+            TarEntry::File(file) => (
+                EntryType::Regular,
+                archive::mode(file)?,
+                file.metadata()?.len(),
+                Box::new(File::open(file)?),
+            ),
+            TarEntry::Data(data) => (
+                EntryType::Regular,
+                0o644,
+                data.len().try_into()?,
+                Box::new(*data),
+            ),
+        };
+
+        let mut header = archive::tar_header(kind, mode, size, mtime)?;
+        builder.append_data(&mut header, path, reader)?;
+    }
+
+    let mut encoder = GzBuilder::new()
+        .mtime(0)
+        .operating_system(255)
+        .write(Vec::new(), Compression::best());
+    encoder.write_all(&builder.into_inner()?)?;
+
+    Ok(encoder.finish()?)
+}
+
+fn rpm(info: &PackageInfo<'_>, files: &Entries<'_>, arch: &str) -> Result<Vec<u8>> {
+    let mut builder = ::rpm::PackageBuilder::new(
+        info.name,
+        &info.version(PackageKind::Rpm),
+        info.license.unwrap_or("Unknown"),
+        arch,
+        info.summary(),
+    );
+
+    builder
+        .using_config(
+            ::rpm::BuildConfig::default()
+                .source_date(u32::try_from(info.mtime.unix_timestamp())?)
+                .compression(::rpm::CompressionType::Gzip),
+        )
+        .release("1");
+
+    if let Some(description) = info.description {
+        builder.description(description);
+    }
+
+    if let Some(homepage) = info.homepage {
+        builder.url(homepage);
+    }
+
+    if let Some(author) = info.authors.first() {
+        builder.packager(author);
+    }
+
+    for depend in info.depends {
+        builder.requires(rpm_dependency(depend)?);
+    }
+
+    let mut files = files.to_vec();
+    files.sort_by_key(|(_, path)| *path);
+
+    for (file, path) in files {
+        let mode = u16::try_from(archive::mode(file)?)?;
+        builder.with_file(file, ::rpm::FileOptions::new(path).permissions(mode))?;
+    }
+
+    let mut data = Vec::new();
+    builder.build()?.write(&mut data)?;
+
+    Ok(data)
+}
+
+/// Parse a dependency like `glibc` or `glibc >= 2.28`.
+fn rpm_dependency(s: &str) -> Result<::rpm::Dependency> {
+    let parts = s.split_whitespace().collect::<Vec<_>>();
+
+    Ok(match parts[..] {
+        [name] => ::rpm::Dependency::any(name),
+        [name, "<", version] => ::rpm::Dependency::less(name, version),
+        [name, "<=", version] => ::rpm::Dependency::less_eq(name, version),
+        [name, "=", version] => ::rpm::Dependency::eq(name, version),
+        [name, ">=", version] => ::rpm::Dependency::greater_eq(name, version),
+        [name, ">", version] => ::rpm::Dependency::greater(name, version),
+        _ => bail!("invalid RPM dependency `{s}`"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::{prelude::*, TempDir};
+    use camino::Utf8PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn parse_package_file() -> Result<()> {
+        let file = "$OUT_DIR/man/*.1:/usr/share/man/man1/".parse::<PackageFile>()?;
+        assert_eq!(
+            PackageFile("$OUT_DIR/man/*.1:usr/share/man/man1".parse()?),
+            file
+        );
+        assert_eq!("$OUT_DIR/man/*.1:/usr/share/man/man1", file.to_string());
+
+        assert!("README.md".parse::<PackageFile>().is_err());
+        assert!("README.md:usr/share/doc".parse::<PackageFile>().is_err());
+        assert!("README.md:/usr/../etc".parse::<PackageFile>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn architectures() -> Result<()> {
+        for (target, deb, rpm) in [
+            ("x86_64-unknown-linux-gnu", "amd64", Some("x86_64")),
+            ("aarch64-unknown-linux-musl", "arm64", Some("aarch64")),
+            ("i686-unknown-linux-gnu", "i386", Some("i686")),
+            ("armv7-unknown-linux-gnueabihf", "armhf", Some("armv7hl")),
+            ("arm-unknown-linux-gnueabi", "armel", None),
+            ("riscv64gc-unknown-linux-gnu", "riscv64", Some("riscv64")),
+            ("powerpc64le-unknown-linux-gnu", "ppc64el", Some("ppc64le")),
+        ] {
+            let target = target.parse()?;
+            assert_eq!(deb, deb_arch(&target)?);
+            assert_eq!(rpm, rpm_arch(&target).ok());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn build_packages() -> Result<()> {
+        let dir = TempDir::new()?;
+        dir.child("demo").write_binary(b"\x7fELF demo")?;
+        dir.child("demo.1").write_str(".TH DEMO 1")?;
+
+        let root = Utf8PathBuf::try_from(dir.to_path_buf())?;
+        let (bin, man) = (root.join("demo"), root.join("demo.1"));
+        let files = [
+            (bin.as_path(), "/usr/bin/demo"),
+            (man.as_path(), "/usr/share/man/man1/demo.1"),
+        ];
+
+        let version = Version::parse("1.2.0-rc.1")?;
+        let authors = ["Jane Doe <jane@example.com>".to_owned()];
+        let deb_depends = ["libc6 (>= 2.31)".to_owned()];
+        let rpm_requires = ["glibc >= 2.28".to_owned()];
+        let info = PackageInfo {
+            name: "demo_tool",
+            version: &version,
+            description: Some("Demo tool\n\nDoes demo things."),
+            license: Some("MIT"),
+            authors: &authors,
+            homepage: Some("https://example.com"),
+            depends: &deb_depends,
+            mtime: OffsetDateTime::from_unix_timestamp(1_700_000_000)?,
+        };
+        let target = "x86_64-unknown-linux-gnu".parse()?;
+
+        let (name, deb) = PackageKind::Deb.build(&info, &files, &target)?;
+        assert_eq!("demo-tool_1.2.0~rc.1-1_amd64.deb", name);
+        assert!(deb.starts_with(
+            b"!<arch>\ndebian-binary   1700000000  0     0     100644  4         `\n2.0\n"
+        ));
+        assert_eq!(deb, PackageKind::Deb.build(&info, &files, &target)?.1);
+
+        let info = info.with_depends(&rpm_requires);
+        let (name, rpm) = PackageKind::Rpm.build(&info, &files, &target)?;
+        assert_eq!("demo_tool-1.2.0~rc.1-1.x86_64.rpm", name);
+        assert_eq!(b"\xed\xab\xee\xdb", &rpm[..4]);
+
+        let version = Version::parse("1.0.0-rc-1+build-7")?;
+        let info = PackageInfo {
+            version: &version,
+            ..info
+        };
+        assert_eq!("1.0.0~rc-1+build-7", info.version(PackageKind::Deb));
+        assert_eq!("1.0.0~rc.1+build.7", info.version(PackageKind::Rpm));
+
+        assert!(rpm_dependency("glibc >= 2.28").is_ok());
+        assert!(rpm_dependency("glibc >=").is_err());
+
+        Ok(())
+    }
+}
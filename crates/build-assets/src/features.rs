@@ -0,0 +1,206 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use actions_common::glob;
+use anyhow::{ensure, Context, Result};
+use cargo_metadata::Package;
+use target_lexicon::Triple;
+
+/// Selection of crate features to activate during compilation.
+#[derive(Default)]
+pub struct Features {
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+    pub all_features: bool,
+}
+
+impl Features {
+    /// Ensure that all features exist in at least one of the given packages, either as explicit
+    /// feature or as implicit feature of an optional dependency. Features of the form
+    /// `<name>/<feature>` must name a dependency, or a workspace member that has the feature.
+    pub fn validate(&self, packages: &[&Package], members: &[&Package]) -> Result<()> {
+        for feature in &self.features {
+            let found = match feature.split_once('/') {
+                Some((name, feature)) => {
+                    if let Some(member) = members.iter().find(|member| member.name == name) {
+                        has_feature(member, feature)
+                    } else {
+                        let dep = name.strip_suffix('?').unwrap_or(name);
+                        packages.iter().any(|pkg| {
+                            pkg.dependencies
+                                .iter()
+                                .any(|d| d.rename.as_deref().unwrap_or(&d.name) == dep)
+                        })
+                    }
+                }
+                None => packages.iter().any(|pkg| has_feature(pkg, feature)),
+            };
+
+            ensure!(
+                found,
+                "feature `{feature}` not found, available features are: {}",
+                packages
+                    .iter()
+                    .flat_map(|pkg| pkg.features.keys())
+                    .map(|feature| format!("`{feature}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn has_feature(package: &Package, feature: &str) -> bool {
+    package.features.contains_key(feature)
+        || package
+            .dependencies
+            .iter()
+            .any(|d| d.optional && d.rename.as_deref().unwrap_or(&d.name) == feature)
+}
+
+/// Additional features that are only activated for targets matching a glob pattern, in the form
+/// `<pattern>=<feature>,<feature>,...`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TargetFeatures {
+    pub pattern: String,
+    pub features: Vec<String>,
+}
+
+impl TargetFeatures {
+    /// Collect the features of all entries that match the given target.
+    pub fn collect(entries: &[Self], target: &Triple) -> Result<Vec<String>> {
+        let target = target.to_string();
+        let mut features = Vec::new();
+
+        for entry in entries {
+            if glob::build_globset(&[&entry.pattern])?.is_match(&target) {
+                features.extend(entry.features.iter().cloned());
+            }
+        }
+
+        Ok(features)
+    }
+}
+
+impl FromStr for TargetFeatures {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, features) = s
+            .split_once('=')
+            .context("missing `=` between target pattern and features")?;
+        let pattern = pattern.trim();
+
+        glob::build_globset(&[pattern])
+            .with_context(|| format!("invalid target pattern `{pattern}`"))?;
+
+        Ok(Self {
+            pattern: pattern.to_owned(),
+            features: features
+                .split(',')
+                .map(str::trim)
+                .filter(|feature| !feature.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        })
+    }
+}
+
+impl Display for TargetFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.pattern, self.features.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, features: &[&str], dependencies: &[&str]) -> Result<Package> {
+        Ok(serde_json::from_value(serde_json::json!({
+            "name": name,
+            "version": "1.0.0",
+            "id": format!("{name} 1.0.0 (path+file:///{name})"),
+            "dependencies": dependencies
+                .iter()
+                .map(|dep| serde_json::json!({
+                    "name": dep,
+                    "req": "^1",
+                    "kind": null,
+                    "optional": true,
+                    "uses_default_features": true,
+                    "features": [],
+                    "target": null,
+                    "rename": null,
+                    "registry": null,
+                }))
+                .collect::<Vec<_>>(),
+            "targets": [],
+            "features": features
+                .iter()
+                .map(|feature| (*feature, Vec::<String>::new()))
+                .collect::<std::collections::BTreeMap<_, _>>(),
+            "manifest_path": format!("/{name}/Cargo.toml"),
+        }))?)
+    }
+
+    #[test]
+    fn validate_features() -> Result<()> {
+        let cli = package("cli", &["color"], &["serde"])?;
+        let core = package("core", &["simd"], &[])?;
+        let members = [&cli, &core];
+        let validate = |features: &[&str]| {
+            Features {
+                features: features.iter().map(|&feature| feature.to_owned()).collect(),
+                ..Features::default()
+            }
+            .validate(&[&cli], &members)
+        };
+
+        assert!(validate(&["color", "serde", "serde/derive", "serde?/derive"]).is_ok());
+        assert!(validate(&["core/simd", "cli/color"]).is_ok());
+        assert!(validate(&["simd"]).is_err());
+        assert!(validate(&["core/color"]).is_err());
+        assert!(validate(&["tokio/full"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::assert_is_empty)]
+    fn target_features() -> Result<()> {
+        let entries = [
+            "*-windows-*=windows-service".parse::<TargetFeatures>()?,
+            "*-linux-musl = vendored, static ".parse()?,
+            "x86_64-unknown-linux-musl=simd".parse()?,
+        ];
+
+        assert_eq!(
+            TargetFeatures {
+                pattern: "*-linux-musl".to_owned(),
+                features: vec!["vendored".to_owned(), "static".to_owned()],
+            },
+            entries[1]
+        );
+        assert_eq!(
+            vec!["windows-service"],
+            TargetFeatures::collect(&entries, &"x86_64-pc-windows-msvc".parse()?)?
+        );
+        assert_eq!(
+            vec!["vendored", "static", "simd"],
+            TargetFeatures::collect(&entries, &"x86_64-unknown-linux-musl".parse()?)?
+        );
+        assert!(
+            TargetFeatures::collect(&entries, &"x86_64-unknown-linux-gnu".parse()?)?.is_empty()
+        );
+
+        assert!("*-windows-*".parse::<TargetFeatures>().is_err());
+        assert!("{]=windows-service".parse::<TargetFeatures>().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    fs,
+    str::FromStr,
+};
+
+use actions_common::glob;
+use anyhow::{bail, ensure, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// Prefix for patterns that are relative to the `OUT_DIR` of the package's build script, instead
+/// of the workspace root.
+const OUT_DIR_PREFIX: &str = "$OUT_DIR/";
+
+/// Extra files to put into the archive next to the binaries, in the form `<glob>[:<dest>]`.
+///
+/// The glob is relative to the workspace root, or to the build script's output directory if it
+/// starts with `$OUT_DIR/`. Matched files keep their relative path inside the archive, unless a
+/// destination directory is given, which they are placed in by file name. The destination is
+/// relative to the archive root, and can't leave it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Include {
+    pub pattern: String,
+    pub out_dir: bool,
+    pub dest: Option<String>,
+}
+
+impl FromStr for Include {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, dest) = match s.split_once(':') {
+            Some((pattern, dest)) => (pattern, Some(dest.trim_end_matches('/').to_owned())),
+            None => (s, None),
+        };
+
+        let (pattern, out_dir) = match pattern.strip_prefix(OUT_DIR_PREFIX) {
+            Some(pattern) => (pattern, true),
+            None => (pattern, false),
+        };
+
+        ensure!(!pattern.is_empty(), "missing glob pattern in `{s}`");
+        ensure!(
+            dest.as_ref().is_none_or(|dest| !dest.is_empty()),
+            "empty destination in `{s}`"
+        );
+        if let Some(dest) = &dest {
+            ensure!(
+                !dest.starts_with('/'),
+                "destination in `{s}` must be relative to the archive root"
+            );
+            ensure!(
+                dest.split('/').all(|part| part != ".."),
+                "destination in `{s}` must not contain `..`"
+            );
+        }
+        glob::build_globset(&[pattern])?;
+
+        Ok(Self {
+            pattern: pattern.to_owned(),
+            out_dir,
+            dest,
+        })
+    }
+}
+
+impl Display for Include {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.out_dir {
+            f.write_str(OUT_DIR_PREFIX)?;
+        }
+
+        f.write_str(&self.pattern)?;
+
+        if let Some(dest) = &self.dest {
+            write!(f, ":{dest}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Include {
+    /// Find all files that match the pattern, as pairs of the file location and the path inside
+    /// the archive. Directories named in `skip`, like the target directory, aren't searched.
+    pub fn resolve(
+        &self,
+        root: &Utf8Path,
+        skip: &[&Utf8Path],
+    ) -> Result<Vec<(Utf8PathBuf, String)>> {
+        let set = glob::build_globset(&[&self.pattern])?;
+
+        // Only search below the part of the pattern that doesn't contain any wildcards.
+        let parts = self.pattern.split('/').collect::<Vec<_>>();
+        let prefix = parts[..parts.len() - 1]
+            .iter()
+            .take_while(|part| !part.contains(['*', '?', '[', '{']))
+            .copied()
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut files = Vec::new();
+        walk(&root.join(prefix), skip, &mut files)?;
+
+        let mut matches = files
+            .into_iter()
+            .filter_map(|file| {
+                let relative = file.strip_prefix(root).ok()?.as_str().replace('\\', "/");
+                set.is_match(&relative).then_some((file, relative))
+            })
+            .map(|(file, relative)| {
+                let path = match (&self.dest, file.file_name()) {
+                    (Some(dest), Some(name)) => format!("{dest}/{name}"),
+                    _ => relative,
+                };
+                (file, path)
+            })
+            .collect::<Vec<_>>();
+        matches.sort_by(|a, b| a.1.cmp(&b.1));
+
+        Ok(matches)
+    }
+}
+
+/// Resolve all includes against the workspace root and the given build script output
+/// directories. Each pattern must match at least one file, and no two different files may end up
+/// at the same path, including the `reserved` entries like the binaries.
+pub fn resolve(
+    includes: &[Include],
+    workspace_root: &Utf8Path,
+    target_dir: &Utf8Path,
+    out_dirs: &[&Utf8Path],
+    reserved: &[(&Utf8Path, &str)],
+) -> Result<Vec<(Utf8PathBuf, String)>> {
+    let mut sources = reserved
+        .iter()
+        .map(|&(file, path)| (path.to_owned(), file.to_owned()))
+        .collect::<BTreeMap<_, _>>();
+    let mut files = Vec::new();
+
+    for include in includes {
+        let found = if include.out_dir {
+            ensure!(
+                !out_dirs.is_empty(),
+                "`{include}` requires a build script, but none of the packages has one"
+            );
+
+            out_dirs
+                .iter()
+                .map(|dir| include.resolve(dir, &[]))
+                .collect::<Result<Vec<_>>>()?
+                .concat()
+        } else {
+            include.resolve(workspace_root, &[target_dir])?
+        };
+
+        ensure!(!found.is_empty(), "`{include}` didn't match any files");
+
+        for (file, path) in found {
+            match sources.get(&path) {
+                // Overlapping patterns may match the same file more than once.
+                Some(existing) if existing == &file => {}
+                Some(existing) => {
+                    bail!("both `{existing}` and `{file}` would be placed at `{path}`")
+                }
+                None => {
+                    sources.insert(path.clone(), file.clone());
+                    files.push((file, path));
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+fn walk(dir: &Utf8Path, skip: &[&Utf8Path], files: &mut Vec<Utf8PathBuf>) -> Result<()> {
+    if !dir.is_dir() || skip.contains(&dir) {
+        return Ok(());
+    }
+
+    for entry in dir
+        .read_dir_utf8()
+        .with_context(|| format!("failed reading directory `{dir}`"))?
+    {
+        let entry = entry?;
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            if entry.file_name() != ".git" {
+                walk(path, skip, files)?;
+            }
+        } else if fs::metadata(path)?.is_file() {
+            files.push(path.to_owned());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::{prelude::*, TempDir};
+
+    use super::*;
+
+    #[test]
+    fn parse_include() -> Result<()> {
+        for (input, pattern, out_dir, dest) in [
+            ("LICENSE*", "LICENSE*", false, None),
+            ("README.md:docs", "README.md", false, Some("docs")),
+            ("$OUT_DIR/completions/*", "completions/*", true, None),
+            (
+                "$OUT_DIR/man/*.1:man/man1/",
+                "man/*.1",
+                true,
+                Some("man/man1"),
+            ),
+        ] {
+            let include = input.parse::<Include>()?;
+            assert_eq!(
+                Include {
+                    pattern: pattern.to_owned(),
+                    out_dir,
+                    dest: dest.map(str::to_owned),
+                },
+                include
+            );
+            assert_eq!(include, include.to_string().parse()?);
+        }
+
+        assert!("".parse::<Include>().is_err());
+        assert!("README.md:".parse::<Include>().is_err());
+        assert!("{]".parse::<Include>().is_err());
+        assert!("README.md:/usr/share/doc".parse::<Include>().is_err());
+        assert!("README.md:docs/../..".parse::<Include>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_files() -> Result<()> {
+        let dir = TempDir::new()?;
+        dir.child("LICENSE-MIT").touch()?;
+        dir.child("LICENSE-APACHE").touch()?;
+        dir.child("docs/guide.md").touch()?;
+        dir.child("docs/LICENSE-MIT").touch()?;
+        dir.child("demo").touch()?;
+        dir.child("target/LICENSE-OLD").touch()?;
+        dir.child("out/completions/demo.bash").touch()?;
+        dir.child("out/completions/_demo").touch()?;
+
+        let root = Utf8PathBuf::try_from(dir.to_path_buf())?;
+        let parse = |includes: &[&str]| {
+            includes
+                .iter()
+                .map(|include| include.parse())
+                .collect::<Result<Vec<Include>>>()
+        };
+        let target_dir = root.join("target");
+        let out_dir = root.join("out");
+        let binary = root.join("target/release/demo");
+
+        let files = resolve(
+            &parse(&[
+                "**/LICENSE*",
+                "LICENSE-MIT",
+                "docs/*.md:doc",
+                "$OUT_DIR/completions/*",
+            ])?,
+            &root,
+            &target_dir,
+            &[&out_dir],
+            &[(&binary, "demo")],
+        )?;
+
+        assert_eq!(
+            vec![
+                "LICENSE-APACHE",
+                "LICENSE-MIT",
+                "docs/LICENSE-MIT",
+                "doc/guide.md",
+                "completions/_demo",
+                "completions/demo.bash",
+            ],
+            files
+                .iter()
+                .map(|(_, path)| path.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(root.join("docs/guide.md"), files[3].0);
+
+        assert!(resolve(&parse(&["*.txt"])?, &root, &target_dir, &[], &[]).is_err());
+        assert!(resolve(&parse(&["$OUT_DIR/*"])?, &root, &target_dir, &[], &[]).is_err());
+
+        let collision = |includes: &[&str], reserved: &[(&Utf8Path, &str)]| {
+            resolve(&parse(includes)?, &root, &target_dir, &[&out_dir], reserved)
+                .map_err(|e| e.to_string())
+                .err()
+                .context("no collision detected")
+        };
+        assert_eq!(
+            format!(
+                "both `{}` and `{}` would be placed at `licenses/LICENSE-MIT`",
+                root.join("LICENSE-MIT"),
+                root.join("docs/LICENSE-MIT"),
+            ),
+            collision(&["LICENSE-MIT:licenses", "docs/LICENSE-MIT:licenses"], &[])?
+        );
+        assert_eq!(
+            format!(
+                "both `{binary}` and `{}` would be placed at `demo`",
+                root.join("demo"),
+            ),
+            collision(&["demo"], &[(&binary, "demo")])?
+        );
+
+        Ok(())
+    }
+}
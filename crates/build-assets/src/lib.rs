@@ -8,7 +8,20 @@
 
 pub mod apt;
 pub mod archive;
+pub mod backend;
 pub mod cargo;
+pub mod debuginfo;
+pub mod distro;
+pub mod features;
+pub mod include;
+pub mod metadata;
+pub mod msrv;
+pub mod naming;
+pub mod provenance;
+pub mod rustc;
 pub mod rustup;
+pub mod sbom;
+pub mod targets;
 pub mod toolchain;
 pub mod triple;
+pub mod windows;
@@ -2,81 +2,1073 @@
 #![deny(rust_2018_idioms, clippy::all, clippy::pedantic)]
 #![allow(clippy::missing_errors_doc)]
 
-use actions_common::{env, http};
-use anyhow::Result;
+use std::{collections::BTreeMap, num::NonZeroUsize};
+
+use actions_common::{
+    env::{self, GithubArgs},
+    glob, http, output,
+};
+use anyhow::{bail, ensure, Context, Result};
 use build_assets::{
-    apt, archive, cargo::CargoBuilder, rustup, toolchain::Toolchain, triple::TripleExt,
+    apt,
+    archive::{self, ArchiveFormat, Archiver},
+    backend::Backend,
+    cargo::{Binary, CargoBuilder},
+    debuginfo::{self, DebugFormat},
+    distro::{PackageFile, PackageInfo, PackageKind},
+    features::{Features, TargetFeatures},
+    include::{self, Include},
+    metadata::{self, BinTarget},
+    msrv::{self, MsrvMode},
+    naming::{self, NameTemplate, NameVars},
+    provenance,
+    rustc::{self, RustcVersion},
+    rustup, sbom,
+    targets::{EnvVar, TargetConfig, TargetTable},
+    toolchain::{Channel, Toolchain, ToolchainFile},
+    triple::TripleExt,
+    windows::{self, App, AppArchive},
 };
-use serde::Deserialize;
-use serde_with::{formats::CommaSeparator, serde_as, DisplayFromStr, StringWithSeparator};
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_metadata::{Metadata, Package};
+use rayon::{prelude::*, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+use serde_with::{
+    formats::{CommaSeparator, SemicolonSeparator},
+    serde_as, DisplayFromStr, StringWithSeparator,
+};
+use sha2::{Digest, Sha256};
+use sign_assets::signer::{Backend as SigningBackend, Signer};
 use target_lexicon::Triple;
-use tracing::info;
+use time::OffsetDateTime;
+use tracing::{error, info, info_span};
 
 #[serde_as]
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
+#[allow(clippy::struct_excessive_bools)]
 struct Opt {
-    #[serde_as(as = "DisplayFromStr")]
+    #[serde_as(as = "Option<DisplayFromStr>")]
     #[serde(default)]
-    toolchain: Toolchain,
-    #[serde_as(as = "DisplayFromStr")]
-    #[serde(default = "Triple::host")]
-    target: Triple,
+    toolchain: Option<Toolchain>,
+    #[serde(default)]
+    msrv: MsrvMode,
+    #[serde_as(as = "StringWithSeparator::<CommaSeparator, Triple>")]
+    #[serde(default = "default_targets")]
+    target: Vec<Triple>,
+    #[serde(default = "default_jobs")]
+    jobs: NonZeroUsize,
     #[serde_as(as = "StringWithSeparator::<CommaSeparator, String>")]
     #[serde(default)]
     features: Vec<String>,
-    bin: String,
+    #[serde(default)]
+    no_default_features: bool,
+    #[serde(default)]
+    all_features: bool,
+    #[serde_as(as = "StringWithSeparator::<SemicolonSeparator, TargetFeatures>")]
+    #[serde(default)]
+    target_features: Vec<TargetFeatures>,
+    #[serde_as(as = "StringWithSeparator::<CommaSeparator, String>")]
+    #[serde(default)]
+    bin: Vec<String>,
+    package: Option<String>,
+    profile: Option<String>,
+    #[serde(default)]
+    build_std: bool,
+    #[serde(default)]
+    backend: Backend,
+    glibc_version: Option<String>,
+    manifest_path: Option<Utf8PathBuf>,
+    target_config: Option<Utf8PathBuf>,
+    #[serde_as(as = "StringWithSeparator::<SemicolonSeparator, EnvVar>")]
+    #[serde(default, skip_serializing)]
+    target_env: Vec<EnvVar>,
+    #[serde(default)]
+    debug_symbols: bool,
+    #[serde(default)]
+    combine: bool,
+    archive: Option<ArchiveFormat>,
+    compression_level: Option<u32>,
+    #[serde_as(as = "StringWithSeparator::<SemicolonSeparator, Include>")]
+    #[serde(default)]
+    include: Vec<Include>,
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default)]
+    name_template: NameTemplate,
+    #[serde(default)]
+    wrap_dir: bool,
+    #[serde_as(as = "StringWithSeparator::<CommaSeparator, PackageKind>")]
+    #[serde(default)]
+    packages: Vec<PackageKind>,
+    #[serde_as(as = "StringWithSeparator::<CommaSeparator, String>")]
+    #[serde(default)]
+    package_targets: Vec<String>,
+    #[serde(default = "default_package_bin_dir")]
+    package_bin_dir: String,
+    #[serde_as(as = "StringWithSeparator::<SemicolonSeparator, PackageFile>")]
+    #[serde(default)]
+    package_files: Vec<PackageFile>,
+    #[serde_as(as = "StringWithSeparator::<CommaSeparator, String>")]
+    #[serde(default)]
+    deb_depends: Vec<String>,
+    #[serde_as(as = "StringWithSeparator::<CommaSeparator, String>")]
+    #[serde(default)]
+    rpm_requires: Vec<String>,
+    #[serde(default)]
+    scoop: bool,
+    #[serde(default)]
+    winget: bool,
+    winget_publisher: Option<String>,
+    #[serde(default)]
+    sbom: bool,
+    #[serde(default)]
+    provenance: bool,
+    #[serde(skip_serializing)]
+    gpg_key: Option<String>,
+    #[serde(skip_serializing)]
+    gpg_passphrase: Option<String>,
+    #[serde(default, skip_serializing)]
+    signing_backend: SigningBackend,
+}
+
+fn default_targets() -> Vec<Triple> {
+    vec![Triple::host()]
+}
+
+fn default_jobs() -> NonZeroUsize {
+    NonZeroUsize::MIN
+}
+
+fn default_package_bin_dir() -> String {
+    "/usr/bin".to_owned()
 }
 
 fn main() -> Result<()> {
     actions_common::tracing::init(env!("CARGO_CRATE_NAME"));
 
+    let started_on = OffsetDateTime::now_utc();
     let opt = env::input::<Opt>()?;
     let github = env::github()?;
 
-    if let Some(pkg) = opt.target.apt_toolchain() {
-        let installed_pkgs = apt::list_packages()?;
-        info!("checked for installed APT packages");
+    ensure!(!opt.target.is_empty(), "at least one target is required");
+    ensure!(
+        opt.package_bin_dir.starts_with('/'),
+        "the binary directory of packages must be absolute"
+    );
+    ensure!(
+        !opt.winget || opt.winget_publisher.is_some(),
+        "winget manifests require the `winget_publisher` input"
+    );
+
+    let metadata = metadata::load(
+        opt.manifest_path.as_deref(),
+        &opt.target[0],
+        &Features::default(),
+    )?;
+    let bin_targets = metadata::resolve_bins(&metadata, opt.package.as_deref(), &opt.bin)?;
+    let bins = bin_targets
+        .iter()
+        .map(|target| target.name.to_owned())
+        .collect::<Vec<_>>();
+    info!(?bins, "resolved binaries to build");
+
+    let table = TargetTable::load(opt.target_config.as_deref())?;
+    let members = metadata.workspace_packages();
+    let plans = opt
+        .target
+        .iter()
+        .map(|target| Plan::new(&opt, &table, &bin_targets, &members, target))
+        .collect::<Result<Vec<_>>>()?;
+    check_archive_names(&opt, &github, &bin_targets, &plans)?;
+    check_archs(&plans)?;
+
+    let msrv = msrv::resolve(opt.msrv, &bin_targets)?;
+    let toolchain = resolve_toolchain(&opt, msrv.as_ref())?;
+    prepare(&opt, &toolchain, &plans)?;
+
+    let rustc = rustc::resolve(&toolchain.toolchain)?;
+    info!(toolchain = %toolchain.toolchain, %rustc, "resolved Rust compiler version");
+
+    if let Some(msrv) = &msrv {
+        msrv::check(&rustc.release, msrv)?;
+        info!(%msrv, "verified minimum supported Rust version");
+    }
+
+    output::set("rustc_version", &rustc.release)?;
+    output::set(
+        "rustc_commit_hash",
+        rustc.commit_hash.as_deref().unwrap_or_default(),
+    )?;
+
+    let source_date = if opt.sbom || plans.iter().any(Plan::stores_timestamps) {
+        let source_date = archive::source_date(&github.sha)?;
+        info!(%source_date, "resolved timestamp for archived files");
+        source_date
+    } else {
+        OffsetDateTime::UNIX_EPOCH
+    };
+
+    // Imported once for all parallel builds, as closing it removes the key from GnuPG again.
+    let signer = provenance_signer(&opt)?;
+
+    let build = Build {
+        opt: &opt,
+        toolchain: &toolchain.toolchain,
+        rustc: &rustc,
+        github: &github,
+        started_on,
+        source_date,
+        bin_targets: &bin_targets,
+        bins: &bins,
+        metadata: &metadata,
+        signer: signer.as_ref(),
+        // Cargo locks the target directory during a build, so parallel builds need their own.
+        target_dir: (opt.jobs.get() > 1).then(|| metadata.target_directory.join("build-assets")),
+    };
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(opt.jobs.get())
+        .build()?;
+    let results = pool.install(|| {
+        plans
+            .par_iter()
+            .map(|plan| build.run(plan))
+            .collect::<Vec<_>>()
+    });
+
+    for (plan, result) in plans.iter().zip(&results) {
+        match result {
+            Ok(output) => {
+                info!(target = %plan.target, files = output.files.len(), "build succeeded");
+            }
+            Err(e) => error!(target = %plan.target, error = format!("{e:#}"), "build failed"),
+        }
+    }
+
+    let published = publish(&opt, &github, &results);
+    if let Some(signer) = signer {
+        signer.close()?;
+    }
+    published?;
+
+    let failed = results.iter().filter(|result| result.is_err()).count();
+    ensure!(
+        failed == 0,
+        "{failed} of {} targets failed to build",
+        plans.len()
+    );
+
+    Ok(())
+}
+
+/// Import the key for signing provenance statements, if they are created and signed at all.
+fn provenance_signer(opt: &Opt) -> Result<Option<Signer<'_>>> {
+    opt.gpg_key
+        .as_deref()
+        .filter(|_| opt.provenance)
+        .map(|key| Signer::import(opt.signing_backend, key, opt.gpg_passphrase.as_deref()))
+        .transpose()
+}
+
+/// Attach all created files to the release, followed by the installer manifests that refer to
+/// the uploaded archives.
+fn publish(opt: &Opt, github: &GithubArgs, results: &[Result<Output<'_>>]) -> Result<()> {
+    let release = http::get_release(&github.token, &github.repository, &github.ref_name)?;
+
+    let mut urls = BTreeMap::new();
+
+    for (name, data) in results.iter().flatten().flat_map(|output| &output.files) {
+        let asset = http::upload_asset(&github.token, &github.repository, release.id, name, data)?;
+        info!(%name, "attached file to release");
+        urls.insert(name.as_str(), asset.browser_download_url);
+    }
+
+    let archives = results
+        .iter()
+        .flatten()
+        .flat_map(|output| &output.manifest_archives)
+        .collect::<Vec<_>>();
+
+    for (name, data) in installer_manifests(opt, &archives, &urls)? {
+        http::upload_asset(&github.token, &github.repository, release.id, &name, &data)?;
+        info!(%name, "attached installer manifest to release");
+    }
+
+    Ok(())
+}
+
+/// Target-specific settings for a single build, resolved and validated upfront.
+struct Plan<'a> {
+    target: &'a Triple,
+    features: Features,
+    archive: ArchiveFormat,
+    packages: Vec<PackageKind>,
+    manifests: bool,
+    debug_format: Option<DebugFormat>,
+    /// Tool to split off debug information, if the debug format requires one.
+    objcopy: Option<String>,
+    config: Option<&'a TargetConfig>,
+    glibc_version: Option<&'a str>,
+}
+
+impl<'a> Plan<'a> {
+    fn new(
+        opt: &'a Opt,
+        table: &'a TargetTable,
+        bin_targets: &[BinTarget<'_>],
+        members: &[&Package],
+        target: &'a Triple,
+    ) -> Result<Self> {
+        let features = Features {
+            features: opt
+                .features
+                .iter()
+                .cloned()
+                .chain(TargetFeatures::collect(&opt.target_features, target)?)
+                .collect(),
+            no_default_features: opt.no_default_features,
+            all_features: opt.all_features,
+        };
+        features.validate(
+            &bin_targets
+                .iter()
+                .map(|target| target.package)
+                .collect::<Vec<_>>(),
+            members,
+        )?;
+        info!(%target, features = ?features.features, "validated crate features");
+
+        let archive = opt
+            .archive
+            .unwrap_or_else(|| ArchiveFormat::for_target(target));
+        archive.check_level(opt.compression_level)?;
+        ensure!(
+            archive != ArchiveFormat::Raw || !opt.combine || bin_targets.len() == 1,
+            "the raw format can't combine multiple binaries into one file"
+        );
+        ensure!(
+            archive != ArchiveFormat::Raw || opt.include.is_empty(),
+            "the raw format can't include extra files"
+        );
+        ensure!(
+            archive != ArchiveFormat::Raw || !opt.wrap_dir,
+            "the raw format can't wrap files in a directory"
+        );
 
-        if installed_pkgs.contains(pkg) {
-            info!(%pkg, "required APT packages already installed");
+        // Distribution packages only exist for Linux, other targets are skipped.
+        let selected = opt.package_targets.is_empty()
+            || glob::build_globset(&opt.package_targets)?.is_match(target.to_string());
+        let packages = if target.is_linux() && selected {
+            opt.packages.clone()
         } else {
-            apt::update()?;
-            info!("updated APT cache");
+            Vec::new()
+        };
+        for kind in &packages {
+            kind.arch(target)?;
+        }
 
-            apt::install_package(pkg)?;
-            info!(%pkg, "installed APT package");
+        let manifests = target.is_windows()
+            && (opt.scoop || opt.winget)
+            && !windows::is_superseded(target, &opt.target);
+        if manifests {
+            ensure!(
+                archive == ArchiveFormat::Zip,
+                "Scoop and winget manifests require zip archives"
+            );
+            windows::check_target(target)?;
         }
+
+        let debug_format = opt
+            .debug_symbols
+            .then(|| DebugFormat::for_target(target))
+            .transpose()?;
+        // Other backends bring their own C toolchain for the target.
+        let config = table
+            .find(target)
+            .filter(|_| opt.backend.uses_host_toolchain());
+
+        Ok(Self {
+            target,
+            features,
+            archive,
+            packages,
+            manifests,
+            debug_format,
+            objcopy: (debug_format == Some(DebugFormat::Objcopy))
+                .then(|| debuginfo::objcopy(config, target)),
+            config,
+            glibc_version: opt
+                .glibc_version
+                .as_deref()
+                .filter(|_| target.is_gnu_linux()),
+        })
     }
 
-    rustup::install_toolchain(&opt.toolchain, &opt.target, &[])?;
-    info!(toolchain = %opt.toolchain, target = %opt.target, "installed Rust toolchain");
+    /// Whether any of the files to publish records file timestamps, which only raw binaries
+    /// don't.
+    fn stores_timestamps(&self) -> bool {
+        self.archive != ArchiveFormat::Raw
+            || self.debug_format.is_some()
+            || !self.packages.is_empty()
+    }
+}
 
-    // let installed_targets = rustup::list_targets()?;
-    // info!("checked for installed Rust targets");
+/// Use the toolchain from the inputs, or the one pinned by the project, falling back to the
+/// latest stable release if neither is given. In [`MsrvMode::Use`], the MSRV replaces the pinned
+/// toolchain, while its components and targets are kept.
+fn resolve_toolchain(opt: &Opt, msrv: Option<&Channel>) -> Result<ToolchainFile> {
+    if opt.msrv == MsrvMode::Use {
+        ensure!(
+            opt.toolchain.is_none(),
+            "the `toolchain` input can't be combined with building on the MSRV"
+        );
+    }
 
-    // if !installed_targets.contains(&opt.target) {
-    //     rustup::install_target(&opt.target)?;
-    //     info!(target = %opt.target, "installed Rust target");
-    // }
+    if let Some(toolchain) = &opt.toolchain {
+        return Ok(toolchain.clone().into());
+    }
 
-    let binary = CargoBuilder::new(&opt.bin, &opt.target)
-        .with_compiler(opt.target.cc_compiler())
-        .with_features(&opt.features)
-        .run()?;
-    info!("compiled binary");
+    let dir = match opt.manifest_path.as_deref().and_then(Utf8Path::parent) {
+        Some(dir) if !dir.as_str().is_empty() => dir.to_owned(),
+        _ => Utf8PathBuf::from("."),
+    };
+    let dir = dir.canonicalize_utf8()?;
 
-    let (name, data) = if opt.target.is_windows() {
-        archive::zip(&binary, opt.bin.as_str(), &opt.target)
+    let mut file = match ToolchainFile::discover(&dir)? {
+        Some(file) => {
+            info!(toolchain = %file.toolchain, "found pinned toolchain");
+            file
+        }
+        None => ToolchainFile::default(),
+    };
+
+    if let Some(msrv) = msrv.filter(|_| opt.msrv == MsrvMode::Use) {
+        file.toolchain = Toolchain::channel(msrv.clone());
+        info!(toolchain = %file.toolchain, "selected MSRV toolchain");
+    }
+
+    Ok(file)
+}
+
+/// Set up all the tools that are required for compilation.
+fn prepare(opt: &Opt, toolchain: &ToolchainFile, plans: &[Plan<'_>]) -> Result<()> {
+    ensure!(
+        !opt.build_std || toolchain.toolchain.channel == Channel::Nightly,
+        "building the standard library requires a nightly toolchain"
+    );
+
+    ensure!(
+        opt.glibc_version.is_none() || opt.backend.supports_glibc_version(),
+        "pinning the glibc version is only supported by the `zigbuild` backend"
+    );
+
+    let mut pkgs = Vec::new();
+
+    for pkg in plans
+        .iter()
+        .filter_map(|plan| plan.config)
+        .flat_map(|config| &config.apt)
+    {
+        if !pkgs.contains(pkg) {
+            pkgs.push(pkg.clone());
+        }
+    }
+
+    // The fallback for targets without a configured `objcopy`.
+    let llvm = "llvm".to_owned();
+    if plans
+        .iter()
+        .any(|plan| plan.objcopy.as_deref() == Some("llvm-objcopy"))
+        && !pkgs.contains(&llvm)
+    {
+        pkgs.push(llvm);
+    }
+
+    if !pkgs.is_empty() {
+        install_apt_packages(&pkgs)?;
+    }
+
+    opt.backend.install()?;
+    info!(backend = ?opt.backend, "prepared build backend");
+
+    let mut targets = toolchain.targets.clone();
+    targets.extend(opt.target.iter().cloned());
+
+    let mut components = toolchain
+        .components
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if opt.build_std {
+        components.push("rust-src");
+    }
+
+    install_rust(toolchain, &targets, &components)?;
+
+    Ok(())
+}
+
+/// Shared state for building, packaging and attesting the binaries of each target.
+struct Build<'a, 's> {
+    opt: &'a Opt,
+    toolchain: &'a Toolchain,
+    rustc: &'a RustcVersion,
+    github: &'a GithubArgs,
+    started_on: OffsetDateTime,
+    source_date: OffsetDateTime,
+    bin_targets: &'a [BinTarget<'a>],
+    bins: &'a [String],
+    metadata: &'a Metadata,
+    /// Key for signing provenance statements, if they are signed.
+    signer: Option<&'s Signer<'s>>,
+    target_dir: Option<Utf8PathBuf>,
+}
+
+/// Files to publish for a single target.
+struct Output<'a> {
+    files: Vec<(String, Vec<u8>)>,
+    /// Archives to describe in installer manifests, once their download URLs are known.
+    manifest_archives: Vec<Archived<'a>>,
+}
+
+/// Archive of a group of binaries, recorded for installer manifests.
+struct Archived<'a> {
+    target: Triple,
+    name: String,
+    package: &'a Package,
+    file: String,
+    sha256: String,
+    bins: Vec<String>,
+    dir: Option<String>,
+}
+
+impl<'a> Build<'a, '_> {
+    /// Compile the binaries for the planned target and create all the files to publish.
+    fn run(&self, plan: &Plan<'_>) -> Result<Output<'a>> {
+        let _span = info_span!("build", target = %plan.target).entered();
+        let opt = self.opt;
+
+        let mut binaries = CargoBuilder::new(opt.backend, self.bins, plan.target)
+            .with_toolchain(self.toolchain)
+            .with_profile(opt.profile.as_deref())
+            .with_build_std(opt.build_std)
+            .with_package(opt.package.as_deref())
+            .with_manifest_path(opt.manifest_path.as_deref())
+            .with_target_dir(&self.target_dir.as_ref().map_or_else(
+                || self.metadata.target_directory.clone(),
+                |dir| dir.join(plan.target.to_string()),
+            ))
+            .with_glibc_version(plan.glibc_version)
+            .with_target_config(plan.config)
+            .with_env(&opt.target_env)
+            .with_features(&plan.features)
+            .with_debug_symbols(plan.debug_format)
+            .run()?;
+        info!(count = binaries.len(), "compiled binaries");
+
+        if let Some(objcopy) = &plan.objcopy {
+            for binary in &mut binaries {
+                debuginfo::split(binary, objcopy)?;
+            }
+            info!("split debug information from binaries");
+        }
+
+        let groups = if opt.combine {
+            let name = combined_name(self.github);
+            vec![(name, binaries.iter().collect::<Vec<_>>())]
+        } else {
+            binaries
+                .iter()
+                .map(|binary| (binary.name.as_str(), vec![binary]))
+                .collect()
+        };
+
+        let metadata = opt
+            .sbom
+            .then(|| metadata::load(opt.manifest_path.as_deref(), plan.target, &plan.features))
+            .transpose()?;
+        let comment = self.rustc.to_string();
+        let archiver = Archiver::new(plan.archive, opt.compression_level, &comment)?
+            .with_wrap_dir(opt.wrap_dir)
+            .with_mtime(self.source_date);
+        let mut files = Vec::new();
+        let mut manifest_archives = Vec::new();
+
+        for (name, binaries) in groups {
+            let archive_name = self.archive_name(plan.target, name, &binaries);
+
+            if plan.debug_format.is_some() {
+                files.push(package_debug(&archiver, &archive_name, &binaries)?);
+            }
+
+            let out_dirs = binaries
+                .iter()
+                .filter_map(|binary| binary.out_dir.as_deref())
+                .collect::<Vec<_>>();
+            let reserved = binaries
+                .iter()
+                .map(|binary| (binary.path.as_path(), binary.file_name()))
+                .collect::<Vec<_>>();
+            let extra = include::resolve(
+                &opt.include,
+                &self.metadata.workspace_root,
+                &self.metadata.target_directory,
+                &out_dirs,
+                &reserved,
+            )?;
+
+            let (archive_name, data) = package(&archiver, &archive_name, &binaries, &extra)?;
+
+            if plan.manifests {
+                let record = self.archived(plan.target, name, &binaries, &archive_name, &data)?;
+                manifest_archives.push(record);
+            }
+
+            if let Some(metadata) = &metadata {
+                files.push(bill_of_materials(
+                    metadata,
+                    self.bin_targets,
+                    &archive_name,
+                    &binaries,
+                    self.source_date,
+                )?);
+            }
+
+            if opt.provenance {
+                files.extend(attest(
+                    opt,
+                    self.signer,
+                    self.github,
+                    self.started_on,
+                    &archive_name,
+                    &data,
+                )?);
+            }
+
+            files.push((archive_name, data));
+
+            for kind in &plan.packages {
+                files.push(self.package_distro(*kind, plan.target, name, &binaries, &out_dirs)?);
+            }
+        }
+
+        Ok(Output {
+            files,
+            manifest_archives,
+        })
+    }
+
+    /// Record an archive for the installer manifests.
+    fn archived(
+        &self,
+        target: &Triple,
+        name: &str,
+        binaries: &[&Binary],
+        file: &str,
+        data: &[u8],
+    ) -> Result<Archived<'a>> {
+        Ok(Archived {
+            target: target.clone(),
+            name: name.to_owned(),
+            package: self
+                .package_of(binaries)
+                .with_context(|| format!("no cargo package found for `{name}`"))?,
+            file: file.to_owned(),
+            sha256: hex::encode(Sha256::digest(data)),
+            bins: binaries
+                .iter()
+                .map(|binary| binary.file_name().to_owned())
+                .collect(),
+            // Manifests only exist for zip archives, which are wrapped in a directory named like
+            // the file itself.
+            dir: self
+                .opt
+                .wrap_dir
+                .then(|| file.trim_end_matches(".zip").to_owned()),
+        })
+    }
+
+    /// Cargo package of a group of binaries, which is the one of the first binary if they are
+    /// combined from several packages.
+    fn package_of(&self, binaries: &[&Binary]) -> Option<&'a Package> {
+        let binary = binaries.first()?;
+
+        self.bin_targets
+            .iter()
+            .find(|target| target.name == binary.name)
+            .map(|target| target.package)
+    }
+
+    /// Name of the archive for a group of binaries, without the file extension.
+    fn archive_name(&self, target: &Triple, name: &str, binaries: &[&Binary]) -> String {
+        archive_name(
+            self.opt,
+            self.github,
+            target,
+            name,
+            self.package_of(binaries),
+        )
+    }
+
+    /// Build a distribution package that installs the binaries and extra files of a group.
+    fn package_distro(
+        &self,
+        kind: PackageKind,
+        target: &Triple,
+        name: &str,
+        binaries: &[&Binary],
+        out_dirs: &[&Utf8Path],
+    ) -> Result<(String, Vec<u8>)> {
+        let opt = self.opt;
+        let package = self
+            .package_of(binaries)
+            .with_context(|| format!("no cargo package found for `{name}`"))?;
+        let depends = match kind {
+            PackageKind::Deb => &opt.deb_depends,
+            PackageKind::Rpm => &opt.rpm_requires,
+        };
+        let info = PackageInfo::new(name, package, self.source_date).with_depends(depends);
+
+        // Paths are relative to the root of the target system until all files are known.
+        let bin_dir = Utf8Path::new(opt.package_bin_dir.trim_matches('/'));
+        let bins = binaries
+            .iter()
+            .map(|binary| (binary.path.as_path(), bin_dir.join(binary.file_name())))
+            .collect::<Vec<_>>();
+        let reserved = bins
+            .iter()
+            .map(|(file, path)| (*file, path.as_str()))
+            .collect::<Vec<_>>();
+        let files = opt
+            .package_files
+            .iter()
+            .map(|file| file.0.clone())
+            .collect::<Vec<_>>();
+        let extra = include::resolve(
+            &files,
+            &self.metadata.workspace_root,
+            &self.metadata.target_directory,
+            out_dirs,
+            &reserved,
+        )?;
+        let paths = reserved
+            .into_iter()
+            .chain(
+                extra
+                    .iter()
+                    .map(|(file, path)| (file.as_path(), path.as_str())),
+            )
+            .map(|(file, path)| (file, format!("/{path}")))
+            .collect::<Vec<_>>();
+        let entries = paths
+            .iter()
+            .map(|(file, path)| (*file, path.as_str()))
+            .collect::<Vec<_>>();
+
+        let (name, data) = kind.build(&info, &entries, target)?;
+        info!(%name, "packaged binaries as {kind} package");
+
+        Ok((name, data))
+    }
+}
+
+/// Name of the single group when all binaries are combined into one archive.
+fn combined_name(github: &GithubArgs) -> &str {
+    github
+        .repository
+        .rsplit_once('/')
+        .map_or(github.repository.as_str(), |(_, name)| name)
+}
+
+fn archive_name(
+    opt: &Opt,
+    github: &GithubArgs,
+    target: &Triple,
+    name: &str,
+    package: Option<&Package>,
+) -> String {
+    let version = package
+        .map(|package| package.version.to_string())
+        .unwrap_or_default();
+
+    opt.name_template.render(&NameVars {
+        bin: name,
+        version: &version,
+        target: &target.to_string(),
+        tag: &github.ref_name,
+    })
+}
+
+/// Render the names of all archives before building, to fail early if two of them are the same.
+fn check_archive_names(
+    opt: &Opt,
+    github: &GithubArgs,
+    bin_targets: &[BinTarget<'_>],
+    plans: &[Plan<'_>],
+) -> Result<()> {
+    let groups = if opt.combine {
+        vec![(
+            combined_name(github),
+            bin_targets.first().map(|target| target.package),
+        )]
     } else {
-        archive::tar_gz(&binary, opt.bin.as_str(), &opt.target)
-    }?;
+        bin_targets
+            .iter()
+            .map(|target| (target.name, Some(target.package)))
+            .collect()
+    };
 
-    info!("packaged binary as archive file");
+    naming::ensure_unique(plans.iter().flat_map(|plan| {
+        groups.iter().map(|&(name, package)| {
+            (
+                archive_name(opt, github, plan.target, name, package),
+                format!("`{name}` for `{}`", plan.target),
+            )
+        })
+    }))
+}
 
-    let release = http::get_release(&github.token, &github.repository, &github.ref_name)?;
-    http::upload_asset(&github.token, &github.repository, release.id, &name, &data)?;
+/// Ensure that no two targets build packages for the same architecture, as they would get the
+/// same file name, and that the installer manifests have one archive per architecture.
+fn check_archs(plans: &[Plan<'_>]) -> Result<()> {
+    windows::check_archs(
+        plans
+            .iter()
+            .filter(|plan| plan.manifests)
+            .map(|plan| plan.target),
+    )?;
+
+    let mut seen = BTreeMap::new();
 
-    info!("attached archive to release");
+    for plan in plans {
+        for kind in &plan.packages {
+            let arch = kind.arch(plan.target)?;
+
+            if let Some(existing) = seen.insert((kind.to_string(), arch), plan.target) {
+                bail!(
+                    "both `{existing}` and `{}` would build {kind} packages for `{arch}`, pick \
+                     one of them with the `package_targets` input",
+                    plan.target
+                );
+            }
+        }
+    }
 
     Ok(())
 }
+
+/// Install the toolchain, or only the missing targets and components if the toolchain is
+/// already present.
+fn install_rust(toolchain: &ToolchainFile, targets: &[Triple], components: &[&str]) -> Result<()> {
+    let installed = rustup::list_toolchains()?;
+    info!("checked for installed Rust toolchains");
+
+    let name = rustup::installed_name(&toolchain.toolchain);
+
+    if let Channel::Custom(name) = &toolchain.toolchain.channel {
+        ensure!(
+            installed.contains(name),
+            "custom toolchain `{name}` must be linked with `rustup toolchain link` before building"
+        );
+    }
+
+    if !name.is_some_and(|name| installed.contains(&name)) {
+        rustup::install_toolchain(
+            &toolchain.toolchain,
+            toolchain.profile.as_deref(),
+            targets,
+            components,
+        )?;
+        info!(toolchain = %toolchain.toolchain, ?components, "installed Rust toolchain");
+        return Ok(());
+    }
+
+    info!(toolchain = %toolchain.toolchain, "Rust toolchain already installed");
+
+    let installed_targets = rustup::list_targets(&toolchain.toolchain)?;
+    let missing = targets
+        .iter()
+        .filter(|target| !installed_targets.contains(*target))
+        .collect::<Vec<_>>();
+
+    if !missing.is_empty() {
+        rustup::install_targets(&toolchain.toolchain, &missing)?;
+
+        let missing = missing.iter().map(ToString::to_string).collect::<Vec<_>>();
+        info!(targets = ?missing, "installed Rust targets");
+    }
+
+    let installed_components = rustup::list_components(&toolchain.toolchain)?;
+    let missing = components
+        .iter()
+        .copied()
+        .filter(|component| {
+            !rustup::has_component(&installed_components, &toolchain.toolchain, component)
+        })
+        .collect::<Vec<_>>();
+
+    if !missing.is_empty() {
+        rustup::install_components(&toolchain.toolchain, &missing)?;
+        info!(components = ?missing, "installed Rust components");
+    }
+
+    Ok(())
+}
+
+fn install_apt_packages(pkgs: &[String]) -> Result<()> {
+    let installed_pkgs = apt::list_packages()?;
+    info!("checked for installed APT packages");
+
+    let missing = pkgs
+        .iter()
+        .filter(|pkg| !installed_pkgs.contains(*pkg))
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        info!(?pkgs, "required APT packages already installed");
+        return Ok(());
+    }
+
+    apt::update()?;
+    info!("updated APT cache");
+
+    for pkg in missing {
+        apt::install_package(pkg)?;
+        info!(%pkg, "installed APT package");
+    }
+
+    Ok(())
+}
+
+fn package(
+    archiver: &Archiver<'_>,
+    name: &str,
+    binaries: &[&Binary],
+    extra: &[(Utf8PathBuf, String)],
+) -> Result<(String, Vec<u8>)> {
+    let entries = binaries
+        .iter()
+        .map(|binary| (binary.path.as_path(), binary.file_name()))
+        .chain(
+            extra
+                .iter()
+                .map(|(file, path)| (file.as_path(), path.as_str())),
+        )
+        .collect::<Vec<_>>();
+
+    let (name, data) = archiver.pack(&entries, name)?;
+    info!(%name, "packaged binaries as archive file");
+
+    Ok((name, data))
+}
+
+fn package_debug(
+    archiver: &Archiver<'_>,
+    name: &str,
+    binaries: &[&Binary],
+) -> Result<(String, Vec<u8>)> {
+    let entries = binaries
+        .iter()
+        .flat_map(|binary| &binary.debug)
+        .filter_map(|file| Some((file.as_path(), file.file_name()?)))
+        .collect::<Vec<_>>();
+
+    let (name, data) = archiver.pack_debug(&entries, &format!("{name}-debug"))?;
+    info!(%name, "packaged debug symbols as archive file");
+
+    Ok((name, data))
+}
+
+/// Create Scoop and winget manifests for each group of binaries that was archived for Windows,
+/// referring to the uploaded archives by their download URL.
+fn installer_manifests(
+    opt: &Opt,
+    archives: &[&Archived<'_>],
+    urls: &BTreeMap<&str, String>,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut apps = BTreeMap::<&str, App<'_>>::new();
+
+    for archived in archives {
+        let Some(url) = urls.get(archived.file.as_str()) else {
+            continue;
+        };
+
+        apps.entry(&archived.name)
+            .or_insert_with(|| App {
+                name: &archived.name,
+                package: archived.package,
+                archives: Vec::new(),
+            })
+            .archives
+            .push(AppArchive {
+                target: &archived.target,
+                url,
+                sha256: archived.sha256.clone(),
+                bins: archived.bins.clone(),
+                dir: archived.dir.as_deref(),
+            });
+    }
+
+    let mut files = Vec::new();
+
+    for app in apps.values() {
+        if opt.scoop {
+            files.push(windows::scoop(app)?);
+        }
+
+        if let Some(publisher) = opt.winget_publisher.as_deref().filter(|_| opt.winget) {
+            files.extend(windows::winget(app, publisher)?);
+        }
+
+        info!(name = app.name, "created installer manifests");
+    }
+
+    Ok(files)
+}
+
+fn bill_of_materials(
+    metadata: &Metadata,
+    targets: &[BinTarget<'_>],
+    archive: &str,
+    binaries: &[&Binary],
+    source_date: OffsetDateTime,
+) -> Result<(String, Vec<u8>)> {
+    let mut packages = Vec::<&Package>::new();
+
+    for target in targets {
+        if binaries.iter().any(|binary| binary.name == target.name)
+            && packages.iter().all(|pkg| pkg.id != target.package.id)
+        {
+            packages.push(target.package);
+        }
+    }
+
+    let name = format!("{archive}.cdx.json");
+    let data = sbom::cyclonedx(metadata, &packages, source_date)?;
+    info!(%name, "created software bill of materials");
+
+    Ok((name, data))
+}
+
+fn attest(
+    opt: &Opt,
+    signer: Option<&Signer<'_>>,
+    github: &GithubArgs,
+    started_on: OffsetDateTime,
+    archive: &str,
+    data: &[u8],
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let name = format!("{archive}.intoto.jsonl");
+    let data = provenance::statement(&[(archive, data)], opt, github, started_on)?;
+    info!(%name, "created provenance statement");
+
+    let mut files = Vec::new();
+
+    if let Some(signer) = signer {
+        files.extend(signer.sign(vec![(name.as_str(), data.as_slice())])?);
+        info!(%name, "signed provenance statement");
+    }
+
+    files.push((name, data));
+
+    Ok(files)
+}
@@ -0,0 +1,167 @@
+use anyhow::{bail, ensure, Context, Result};
+use camino::Utf8Path;
+use cargo_metadata::{CargoOpt, Metadata, MetadataCommand, Package};
+use target_lexicon::Triple;
+
+use crate::features::Features;
+
+/// Load the workspace metadata with the dependency graph resolved for the given target and
+/// features.
+pub fn load(
+    manifest_path: Option<&Utf8Path>,
+    target: &Triple,
+    features: &Features,
+) -> Result<Metadata> {
+    let mut cmd = MetadataCommand::new();
+    cmd.other_options(vec!["--filter-platform".to_owned(), target.to_string()]);
+
+    if let Some(path) = manifest_path {
+        cmd.manifest_path(path);
+    }
+
+    if !features.features.is_empty() {
+        cmd.features(CargoOpt::SomeFeatures(features.features.clone()));
+    }
+
+    if features.no_default_features {
+        cmd.features(CargoOpt::NoDefaultFeatures);
+    }
+
+    if features.all_features {
+        cmd.features(CargoOpt::AllFeatures);
+    }
+
+    cmd.exec().context("failed loading cargo metadata")
+}
+
+/// Binary target together with the package that defines it.
+#[derive(Clone, Copy)]
+pub struct BinTarget<'a> {
+    pub name: &'a str,
+    pub package: &'a Package,
+}
+
+/// Find the packages for all the given binaries, the same way as cargo would select them.
+///
+/// If a package is given, all binaries must be part of it. Otherwise, the root package is used,
+/// or all workspace members if the workspace is virtual. Without any binaries, all binaries of the
+/// selected packages are returned.
+pub fn resolve_bins<'a>(
+    metadata: &'a Metadata,
+    package: Option<&str>,
+    bins: &[String],
+) -> Result<Vec<BinTarget<'a>>> {
+    let packages = match (package, metadata.root_package()) {
+        (Some(name), _) => vec![find_package(metadata, name)?],
+        (None, Some(root)) => vec![root],
+        (None, None) => metadata.workspace_packages(),
+    };
+
+    let available = packages
+        .iter()
+        .flat_map(|pkg| {
+            pkg.targets
+                .iter()
+                .filter(|target| target.kind == ["bin"])
+                .map(move |target| BinTarget {
+                    name: &target.name,
+                    package: pkg,
+                })
+        })
+        .collect::<Vec<_>>();
+
+    if bins.is_empty() {
+        ensure!(
+            !available.is_empty(),
+            "no binaries found in {}",
+            describe(&packages)
+        );
+        return Ok(available);
+    }
+
+    bins.iter()
+        .map(|bin| {
+            let mut matches = available.iter().filter(|target| target.name == bin);
+
+            match (matches.next(), matches.next()) {
+                (Some(target), None) => Ok(*target),
+                (Some(first), Some(second)) => bail!(
+                    "binary `{bin}` is defined in both package `{}` and `{}`, select one of them \
+                     with the `package` input",
+                    first.package.name,
+                    second.package.name,
+                ),
+                (None, _) => bail!(
+                    "binary `{bin}` not found in {}, available binaries are: {}",
+                    describe(&packages),
+                    available
+                        .iter()
+                        .map(|target| format!("`{}`", target.name))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+            }
+        })
+        .collect()
+}
+
+fn find_package<'a>(metadata: &'a Metadata, name: &str) -> Result<&'a Package> {
+    let members = metadata.workspace_packages();
+
+    members
+        .iter()
+        .find(|pkg| pkg.name == name)
+        .copied()
+        .with_context(|| {
+            format!(
+                "package `{name}` not found in workspace, available packages are: {}",
+                members
+                    .iter()
+                    .map(|pkg| format!("`{}`", pkg.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+fn describe(packages: &[&Package]) -> String {
+    match packages {
+        [pkg] => format!("package `{}`", pkg.name),
+        _ => "workspace".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_workspace_bins() -> Result<()> {
+        let metadata = load(
+            Some("../../Cargo.toml".into()),
+            &Triple::host(),
+            &Features::default(),
+        )?;
+
+        let bins = resolve_bins(&metadata, None, &[])?;
+        let mut names = bins.iter().map(|bin| bin.name).collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(vec!["build-assets", "hash-assets", "sign-assets"], names);
+
+        let bins = ["hash-assets".to_owned()];
+        let bins = resolve_bins(&metadata, Some("hash-assets"), &bins)?;
+        assert_eq!("hash-assets", bins[0].package.name);
+
+        let bins = ["hash-assets".to_owned()];
+        let err = resolve_bins(&metadata, Some("sign-assets"), &bins)
+            .err()
+            .context("missing error")?;
+        assert_eq!(
+            "binary `hash-assets` not found in package `sign-assets`, available binaries are: \
+             `sign-assets`",
+            err.to_string()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use anyhow::{bail, ensure, Result};
+use cargo_metadata::semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::{metadata::BinTarget, toolchain::Channel};
+
+/// How the minimum supported Rust version (MSRV), as declared by the `rust-version` field of the
+/// packages, is taken into account.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MsrvMode {
+    /// Ignore the MSRV.
+    #[default]
+    Off,
+    /// Fail if the toolchain is older than the MSRV.
+    Check,
+    /// Build with the MSRV toolchain, instead of the requested or pinned one.
+    Use,
+}
+
+/// Find the MSRV for building all the given binaries together, which is the highest
+/// `rust-version` of their packages. Nothing is returned if the mode is [`MsrvMode::Off`].
+pub fn resolve(mode: MsrvMode, targets: &[BinTarget<'_>]) -> Result<Option<Channel>> {
+    if mode == MsrvMode::Off {
+        return Ok(None);
+    }
+
+    let Some(version) = targets
+        .iter()
+        .filter_map(|target| target.package.rust_version.as_ref())
+        .max()
+    else {
+        bail!("none of the packages declares a `rust-version` in its manifest");
+    };
+
+    channel(version).map(Some)
+}
+
+/// Ensure that the release of the compiler, as reported by `rustc`, is at least the MSRV.
+/// Pre-releases count as the version they lead up to, the same way cargo treats them.
+pub fn check(release: &str, msrv: &Channel) -> Result<()> {
+    let version = release
+        .split_once('-')
+        .map_or(release, |(version, _)| version);
+    let channel = version.parse::<Channel>()?;
+
+    ensure!(
+        channel >= *msrv,
+        "rustc {release} is older than the minimum supported Rust version {msrv}"
+    );
+
+    Ok(())
+}
+
+fn channel(version: &Version) -> Result<Channel> {
+    Ok(Channel::Full {
+        major: version.major.try_into()?,
+        minor: version.minor.try_into()?,
+        patch: version.patch.try_into()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_release() -> Result<()> {
+        let msrv = channel(&Version::new(1, 70, 0))?;
+        assert_eq!("1.70.0", msrv.to_string());
+
+        check("1.70.0", &msrv)?;
+        check("1.76.2", &msrv)?;
+        check("1.70.0-nightly", &msrv)?;
+        check("1.71.0-beta.3", &msrv)?;
+        assert!(check("1.69.0", &msrv).is_err());
+        assert!(check("1.65.0-nightly", &msrv).is_err());
+        assert!(channel(&Version::new(1, 300, 0)).is_err());
+
+        Ok(())
+    }
+}
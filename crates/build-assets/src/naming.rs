@@ -0,0 +1,153 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Result};
+
+/// Template for the names of archives, like `{bin}-{version}-{target}`.
+///
+/// The following placeholders are available:
+///
+/// - `{bin}`: name of the binary, or of the repository if all binaries are combined.
+/// - `{version}`: version of the binary's package.
+/// - `{target}`: target triple that the binary is built for.
+/// - `{tag}`: name of the tag or branch that the workflow runs for, with slashes replaced by
+///   dashes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameTemplate(String);
+
+/// Values to replace the placeholders of a [`NameTemplate`] with.
+pub struct NameVars<'a> {
+    pub bin: &'a str,
+    pub version: &'a str,
+    pub target: &'a str,
+    pub tag: &'a str,
+}
+
+impl NameTemplate {
+    /// Replace all placeholders with their values.
+    #[must_use]
+    pub fn render(&self, vars: &NameVars<'_>) -> String {
+        self.0
+            .replace("{bin}", vars.bin)
+            .replace("{version}", vars.version)
+            .replace("{target}", vars.target)
+            .replace("{tag}", &vars.tag.replace('/', "-"))
+    }
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        Self("{bin}-{target}".to_owned())
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = s;
+
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .with_context(|| format!("unclosed placeholder in `{s}`"))?;
+            let name = &rest[start + 1..start + end];
+
+            if !matches!(name, "bin" | "version" | "target" | "tag") {
+                bail!("unknown placeholder `{{{name}}}` in `{s}`");
+            }
+
+            rest = &rest[start + end + 1..];
+        }
+
+        if s.contains(['/', '\\']) {
+            bail!("name template `{s}` must not contain path separators");
+        }
+
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Ensure that all rendered names are distinct, as archives with the same name would replace each
+/// other in the release. Each name comes with a description of what it was rendered for.
+pub fn ensure_unique(names: impl IntoIterator<Item = (String, String)>) -> Result<()> {
+    let mut seen = BTreeMap::new();
+
+    for (name, source) in names {
+        if let Some(existing) = seen.get(&name) {
+            bail!(
+                "both {existing} and {source} would be named `{name}`, add the `{{bin}}` or \
+                 `{{target}}` placeholder to the name template"
+            );
+        }
+
+        seen.insert(name, source);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_template() -> Result<()> {
+        let vars = NameVars {
+            bin: "demo",
+            version: "1.2.0",
+            target: "x86_64-unknown-linux-gnu",
+            tag: "v1.2.0",
+        };
+
+        assert_eq!(
+            "demo-x86_64-unknown-linux-gnu",
+            NameTemplate::default().render(&vars)
+        );
+        assert_eq!(
+            "demo-1.2.0-x86_64-unknown-linux-gnu",
+            "{bin}-{version}-{target}"
+                .parse::<NameTemplate>()?
+                .render(&vars)
+        );
+        assert_eq!(
+            "demo_v1.2.0",
+            "{bin}_{tag}".parse::<NameTemplate>()?.render(&vars)
+        );
+
+        assert!("{bin}-{arch}".parse::<NameTemplate>().is_err());
+        assert!("{bin}-{target".parse::<NameTemplate>().is_err());
+        assert!("dist/{bin}".parse::<NameTemplate>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn unique_names() -> Result<()> {
+        let name = |name: &str, source: &str| (name.to_owned(), source.to_owned());
+
+        assert!(ensure_unique([name("a-x86_64", "`a`"), name("b-x86_64", "`b`")]).is_ok());
+        assert_eq!(
+            "both `a` for `x86_64` and `a` for `aarch64` would be named `a-1.0.0`, add the \
+             `{bin}` or `{target}` placeholder to the name template",
+            ensure_unique([
+                name("a-1.0.0", "`a` for `x86_64`"),
+                name("a-1.0.0", "`a` for `aarch64`"),
+            ])
+            .err()
+            .context("no collision detected")?
+            .to_string()
+        );
+
+        Ok(())
+    }
+}
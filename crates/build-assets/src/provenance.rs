@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+
+use actions_common::env::GithubArgs;
+use anyhow::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v0.1";
+const PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v0.2";
+const BUILD_TYPE: &str = "https://github.com/dnaka91/actions/build-assets@v1";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Statement<'a, P> {
+    #[serde(rename = "_type")]
+    ty: &'static str,
+    subject: Vec<Subject<'a>>,
+    predicate_type: &'static str,
+    predicate: Predicate<'a, P>,
+}
+
+#[derive(Serialize)]
+struct Subject<'a> {
+    name: &'a str,
+    digest: BTreeMap<&'static str, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Predicate<'a, P> {
+    builder: Builder,
+    build_type: &'static str,
+    invocation: Invocation<'a, P>,
+    metadata: Metadata,
+    materials: Vec<Material>,
+}
+
+#[derive(Serialize)]
+struct Builder {
+    id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Invocation<'a, P> {
+    config_source: Material,
+    parameters: &'a P,
+}
+
+#[derive(Serialize)]
+struct Metadata {
+    #[serde(rename = "buildInvocationId")]
+    invocation_id: String,
+    #[serde(rename = "buildStartedOn", with = "time::serde::rfc3339")]
+    started_on: OffsetDateTime,
+    #[serde(rename = "buildFinishedOn", with = "time::serde::rfc3339")]
+    finished_on: OffsetDateTime,
+}
+
+#[derive(Clone, Serialize)]
+struct Material {
+    uri: String,
+    digest: BTreeMap<&'static str, String>,
+}
+
+/// Create an in-toto statement with SLSA provenance for the given archives, in JSON Lines format.
+///
+/// The `parameters` are the user-provided inputs of the build and are recorded as-is, so they must
+/// not contain any secrets.
+pub fn statement<P: Serialize>(
+    archives: &[(&str, &[u8])],
+    parameters: &P,
+    github: &GithubArgs,
+    started_on: OffsetDateTime,
+) -> Result<Vec<u8>> {
+    let source = Material {
+        uri: format!(
+            "git+{}/{}@{}",
+            github.server_url, github.repository, github.ref_name
+        ),
+        digest: BTreeMap::from([("sha1", github.sha.clone())]),
+    };
+
+    let statement = Statement {
+        ty: STATEMENT_TYPE,
+        subject: archives
+            .iter()
+            .map(|(name, data)| Subject {
+                name,
+                digest: BTreeMap::from([("sha256", hex::encode(Sha256::digest(data)))]),
+            })
+            .collect(),
+        predicate_type: PREDICATE_TYPE,
+        predicate: Predicate {
+            builder: Builder {
+                id: format!(
+                    "{}/{}/actions/runs/{}",
+                    github.server_url, github.repository, github.run_id
+                ),
+            },
+            build_type: BUILD_TYPE,
+            invocation: Invocation {
+                config_source: source.clone(),
+                parameters,
+            },
+            metadata: Metadata {
+                invocation_id: format!("{}-{}", github.run_id, github.run_attempt),
+                started_on,
+                finished_on: OffsetDateTime::now_utc(),
+            },
+            materials: vec![source],
+        },
+    };
+
+    let mut data = serde_json::to_vec(&statement)?;
+    data.push(b'\n');
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn create_statement() -> Result<()> {
+        let github = GithubArgs {
+            token: String::new(),
+            repository: "dnaka91/actions".to_owned(),
+            ref_name: "v1.0.0".to_owned(),
+            sha: "0123456789abcdef".to_owned(),
+            server_url: "https://github.com".to_owned(),
+            run_id: "42".to_owned(),
+            run_attempt: "1".to_owned(),
+        };
+        let archives = [("app-x86_64-unknown-linux-gnu.tar.gz", b"data".as_slice())];
+
+        let data = statement(
+            &archives,
+            &json!({ "bin": "app" }),
+            &github,
+            datetime!(2024-03-01 12:00 UTC),
+        )?;
+        assert_eq!(Some(&b'\n'), data.last());
+
+        let value = serde_json::from_slice::<Value>(&data)?;
+        assert_eq!(
+            json!([{
+                "name": "app-x86_64-unknown-linux-gnu.tar.gz",
+                "digest": {
+                    "sha256": "3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7"
+                }
+            }]),
+            value["subject"]
+        );
+        assert_eq!(
+            json!({
+                "configSource": {
+                    "uri": "git+https://github.com/dnaka91/actions@v1.0.0",
+                    "digest": { "sha1": "0123456789abcdef" }
+                },
+                "parameters": { "bin": "app" }
+            }),
+            value["predicate"]["invocation"]
+        );
+        assert_eq!(
+            "2024-03-01T12:00:00Z",
+            value["predicate"]["metadata"]["buildStartedOn"]
+        );
+
+        Ok(())
+    }
+}
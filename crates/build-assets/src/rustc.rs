@@ -0,0 +1,213 @@
+use std::{
+    fmt::{self, Display},
+    process::Command,
+};
+
+use anyhow::{ensure, Context, Result};
+use camino::Utf8PathBuf;
+use serde::Deserialize;
+
+use crate::toolchain::Toolchain;
+
+/// Concrete compiler release that a toolchain resolves to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RustcVersion {
+    /// Version number, like `1.76.0` or `1.78.0-nightly`.
+    pub release: String,
+    /// Git commit that the compiler was built from. Channel manifests only contain the
+    /// abbreviated hash.
+    pub commit_hash: Option<String>,
+    /// Date of the commit, in `YYYY-MM-DD` format.
+    pub commit_date: Option<String>,
+}
+
+impl RustcVersion {
+    /// Parse the `rustc` package version from a channel manifest, like the
+    /// `channel-rust-stable.toml` files published for each release.
+    pub fn from_manifest(content: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Manifest {
+            pkg: Packages,
+        }
+
+        #[derive(Deserialize)]
+        struct Packages {
+            rustc: Package,
+        }
+
+        #[derive(Deserialize)]
+        struct Package {
+            version: String,
+        }
+
+        let manifest = toml::from_str::<Manifest>(content)?;
+        let version = manifest.pkg.rustc.version;
+
+        let (release, commit) = match version.split_once(' ') {
+            Some((release, commit)) => (release, Some(commit)),
+            None => (version.as_str(), None),
+        };
+        let commit = commit
+            .and_then(|commit| commit.strip_prefix('('))
+            .and_then(|commit| commit.strip_suffix(')'))
+            .and_then(|commit| commit.split_once(' '));
+
+        Ok(Self {
+            release: release.to_owned(),
+            commit_hash: commit.map(|(hash, _)| hash.to_owned()),
+            commit_date: commit.map(|(_, date)| date.to_owned()),
+        })
+    }
+
+    /// Parse the output of `rustc -vV`.
+    pub fn from_verbose(output: &str) -> Result<Self> {
+        let field = |name: &str| {
+            output.lines().find_map(|line| {
+                line.trim()
+                    .strip_prefix(name)
+                    .and_then(|value| value.strip_prefix(": "))
+                    .filter(|value| *value != "unknown")
+                    .map(str::to_owned)
+            })
+        };
+
+        Ok(Self {
+            release: field("release").context("missing release in rustc version info")?,
+            commit_hash: field("commit-hash"),
+            commit_date: field("commit-date"),
+        })
+    }
+
+    /// Fill in the commit details that are missing, from another source for the same toolchain.
+    #[must_use]
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            release: self.release,
+            commit_hash: self.commit_hash.or(other.commit_hash),
+            commit_date: self.commit_date.or(other.commit_date),
+        }
+    }
+}
+
+impl Display for RustcVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rustc {}", self.release)?;
+
+        if let (Some(hash), Some(date)) = (&self.commit_hash, &self.commit_date) {
+            let hash = hash.get(..9).unwrap_or(hash);
+            write!(f, " ({hash} {date})")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Find out which compiler release the installed toolchain provides. `rustc -vV` is preferred,
+/// as it reports the full commit hash. Details it lacks are taken from the channel manifest that
+/// rustup places in the sysroot, if there is one.
+pub fn resolve(toolchain: &Toolchain) -> Result<RustcVersion> {
+    let version = RustcVersion::from_verbose(&run(toolchain, &["-vV"])?)?;
+
+    if version.commit_hash.is_some() && version.commit_date.is_some() {
+        return Ok(version);
+    }
+
+    let sysroot = run(toolchain, &["--print", "sysroot"])?;
+    let manifest =
+        Utf8PathBuf::from(sysroot.trim()).join("lib/rustlib/multirust-channel-manifest.toml");
+
+    if !manifest.exists() {
+        return Ok(version);
+    }
+
+    let content = std::fs::read_to_string(&manifest)
+        .with_context(|| format!("failed reading channel manifest `{manifest}`"))?;
+
+    Ok(version.or(RustcVersion::from_manifest(&content)?))
+}
+
+fn run(toolchain: &Toolchain, args: &[&str]) -> Result<String> {
+    let output = Command::new("rustc")
+        .env("RUSTUP_TOOLCHAIN", toolchain.to_string())
+        .args(args)
+        .output()?;
+    ensure!(
+        output.status.success(),
+        "failed querying rustc: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_versions() -> Result<()> {
+        let manifest = r#"
+            manifest-version = "2"
+            date = "2024-02-08"
+
+            [pkg.cargo]
+            version = "1.76.0 (c84b36747 2024-01-18)"
+
+            [pkg.rustc]
+            version = "1.76.0 (07dca489a 2024-02-04)"
+
+            [pkg.rustc.target.x86_64-unknown-linux-gnu]
+            available = true
+            "#;
+        let verbose = "
+            rustc 1.76.0 (07dca489a 2024-02-04)
+            binary: rustc
+            commit-hash: 07dca489ac2d933c78d3c5158e3f43beefeb02ce
+            commit-date: 2024-02-04
+            host: x86_64-unknown-linux-gnu
+            release: 1.76.0
+            LLVM version: 17.0.6
+            ";
+
+        let from_manifest = RustcVersion::from_manifest(manifest)?;
+        let from_verbose = RustcVersion::from_verbose(verbose)?;
+
+        assert_eq!(
+            RustcVersion {
+                release: "1.76.0".to_owned(),
+                commit_hash: Some("07dca489a".to_owned()),
+                commit_date: Some("2024-02-04".to_owned()),
+            },
+            from_manifest
+        );
+        assert_eq!(
+            Some("07dca489ac2d933c78d3c5158e3f43beefeb02ce"),
+            from_verbose.commit_hash.as_deref()
+        );
+        assert_eq!(from_manifest.to_string(), from_verbose.to_string());
+        assert_eq!(
+            "rustc 1.76.0 (07dca489a 2024-02-04)",
+            from_manifest.to_string()
+        );
+
+        let custom = RustcVersion::from_verbose(
+            "rustc 1.78.0-dev\nbinary: rustc\ncommit-hash: unknown\ncommit-date: \
+             unknown\nrelease: 1.78.0-dev\n",
+        )?;
+        assert_eq!("rustc 1.78.0-dev", custom.to_string());
+
+        assert_eq!(
+            from_verbose.clone(),
+            from_verbose.clone().or(from_manifest.clone())
+        );
+        assert_eq!(
+            RustcVersion {
+                release: "1.78.0-dev".to_owned(),
+                ..from_manifest.clone()
+            },
+            custom.or(from_manifest)
+        );
+
+        Ok(())
+    }
+}
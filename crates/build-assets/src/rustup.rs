@@ -1,5 +1,6 @@
 use std::{
     collections::HashSet,
+    hash::BuildHasher,
     io::{BufRead, Cursor},
     process::Command,
 };
@@ -7,7 +8,7 @@ use std::{
 use anyhow::{ensure, Result};
 use target_lexicon::Triple;
 
-use crate::toolchain::Toolchain;
+use crate::toolchain::{Channel, Host, Toolchain};
 
 pub fn list_toolchains() -> Result<HashSet<String>> {
     let output = Command::new("rustup")
@@ -35,14 +36,18 @@ pub fn list_toolchains() -> Result<HashSet<String>> {
 
 pub fn install_toolchain(
     toolchain: &Toolchain,
-    target: &Triple,
+    profile: Option<&str>,
+    targets: &[Triple],
     components: &[&str],
 ) -> Result<()> {
     let mut cmd = Command::new("rustup");
     cmd.args(["toolchain", "install"]);
     cmd.arg(toolchain.to_string());
-    cmd.args(["--profile", "minimal"]);
-    cmd.arg("--target").arg(target.to_string());
+    cmd.args(["--profile", profile.unwrap_or("minimal")]);
+
+    for target in targets {
+        cmd.arg("--target").arg(target.to_string());
+    }
 
     for component in components {
         cmd.args(["--component", component]);
@@ -58,9 +63,10 @@ pub fn install_toolchain(
     Ok(())
 }
 
-pub fn list_targets() -> Result<HashSet<Triple>> {
+pub fn list_targets(toolchain: &Toolchain) -> Result<HashSet<Triple>> {
     let output = Command::new("rustup")
-        .args(["target", "list", "--installed"])
+        .args(["target", "list", "--installed", "--toolchain"])
+        .arg(toolchain.to_string())
         .output()?;
     ensure!(
         output.status.success(),
@@ -74,16 +80,110 @@ pub fn list_targets() -> Result<HashSet<Triple>> {
         .collect()
 }
 
-pub fn install_target(target: &Triple) -> Result<()> {
+pub fn install_targets(toolchain: &Toolchain, targets: &[&Triple]) -> Result<()> {
     let output = Command::new("rustup")
-        .args(["target", "add"])
-        .arg(target.to_string())
+        .args(["target", "add", "--toolchain"])
+        .arg(toolchain.to_string())
+        .args(targets.iter().map(ToString::to_string))
         .output()?;
     ensure!(
         output.status.success(),
-        "failed adding target: {:?}",
+        "failed adding targets: {:?}",
         String::from_utf8_lossy(&output.stderr)
     );
 
     Ok(())
 }
+
+pub fn list_components(toolchain: &Toolchain) -> Result<HashSet<String>> {
+    let output = Command::new("rustup")
+        .args(["component", "list", "--installed", "--toolchain"])
+        .arg(toolchain.to_string())
+        .output()?;
+    ensure!(
+        output.status.success(),
+        "failed listing installed components: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Cursor::new(&output.stdout)
+        .lines()
+        .map(|line| line.map_err(Into::into))
+        .collect()
+}
+
+pub fn install_components(toolchain: &Toolchain, components: &[&str]) -> Result<()> {
+    let output = Command::new("rustup")
+        .args(["component", "add", "--toolchain"])
+        .arg(toolchain.to_string())
+        .args(components)
+        .output()?;
+    ensure!(
+        output.status.success(),
+        "failed adding components: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(())
+}
+
+/// Name of the toolchain as listed by rustup, which always includes the full host triple for
+/// official toolchains. Partial hosts are completed by rustup in ways that can't be predicted
+/// reliably, so no name is given for them.
+#[must_use]
+pub fn installed_name(toolchain: &Toolchain) -> Option<String> {
+    match (&toolchain.channel, &toolchain.host) {
+        (Channel::Custom(name), _) => Some(name.clone()),
+        (_, Some(Host::Full(_))) => Some(toolchain.to_string()),
+        (_, Some(Host::Partial(_))) => None,
+        (_, None) => Some(format!("{toolchain}-{}", Triple::host())),
+    }
+}
+
+/// Check whether a component is part of the installed ones. Host-specific components like
+/// `clippy` are listed with the host triple as suffix, while others like `rust-src` are not.
+#[must_use]
+pub fn has_component<S: BuildHasher>(
+    installed: &HashSet<String, S>,
+    toolchain: &Toolchain,
+    component: &str,
+) -> bool {
+    let host = match &toolchain.host {
+        Some(Host::Full(host)) => host.clone(),
+        _ => Triple::host(),
+    };
+
+    installed.contains(component) || installed.contains(&format!("{component}-{host}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_installed() -> Result<()> {
+        let host = Triple::host();
+        let stable = Toolchain::channel(Channel::Stable);
+        let nightly = Toolchain::with_host(Channel::Nightly, "aarch64-apple-darwin".parse()?);
+
+        assert_eq!(Some(format!("stable-{host}")), installed_name(&stable));
+        assert_eq!(
+            Some("nightly-aarch64-apple-darwin".to_owned()),
+            installed_name(&nightly)
+        );
+        assert_eq!(None, installed_name(&"beta-msvc".parse()?));
+        assert_eq!(
+            Some("my-toolchain".to_owned()),
+            installed_name(&"my-toolchain".parse()?)
+        );
+
+        let installed = ["rust-src".to_owned(), format!("clippy-{host}")].into();
+
+        assert!(has_component(&installed, &stable, "rust-src"));
+        assert!(has_component(&installed, &stable, "clippy"));
+        assert!(!has_component(&installed, &stable, "rustfmt"));
+        assert!(!has_component(&installed, &nightly, "clippy"));
+
+        Ok(())
+    }
+}
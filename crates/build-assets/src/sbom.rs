@@ -0,0 +1,213 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use anyhow::{Context, Result};
+use cargo_metadata::{DependencyKind, Metadata, Package, PackageId};
+use serde::Serialize;
+use time::OffsetDateTime;
+
+const SPEC_VERSION: &str = "1.5";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Bom<'a> {
+    #[serde(rename = "bomFormat")]
+    format: &'static str,
+    spec_version: &'static str,
+    version: u32,
+    metadata: BomMetadata<'a>,
+    components: Vec<Component<'a>>,
+    dependencies: Vec<Dependency<'a>>,
+}
+
+#[derive(Serialize)]
+struct BomMetadata<'a> {
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+    tools: Vec<Tool>,
+    component: Component<'a>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+struct Component<'a> {
+    #[serde(rename = "type")]
+    ty: &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref: &'a str,
+    name: &'a str,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    licenses: Vec<License<'a>>,
+    purl: String,
+}
+
+impl<'a> Component<'a> {
+    fn new(ty: &'static str, pkg: &'a Package) -> Self {
+        Self {
+            ty,
+            bom_ref: &pkg.id.repr,
+            name: &pkg.name,
+            version: pkg.version.to_string(),
+            description: pkg.description.as_deref(),
+            licenses: pkg
+                .license
+                .as_deref()
+                .map(|expression| License { expression })
+                .into_iter()
+                .collect(),
+            purl: format!("pkg:cargo/{}@{}", pkg.name, pkg.version),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct License<'a> {
+    expression: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Dependency<'a> {
+    #[serde(rename = "ref")]
+    reference: &'a str,
+    depends_on: Vec<&'a str>,
+}
+
+/// Create a [CycloneDX](https://cyclonedx.org) SBOM in JSON format for the given packages.
+///
+/// The first package describes the SBOM itself, while any further packages are listed as
+/// additional application components. Only the dependencies that end up in the final binary are
+/// recorded. That means build and dev dependencies are skipped, and platform-specific dependencies
+/// must already be filtered out when loading the metadata. The timestamp should be the source
+/// date of the release, to keep the SBOM reproducible.
+pub fn cyclonedx(
+    metadata: &Metadata,
+    packages: &[&Package],
+    timestamp: OffsetDateTime,
+) -> Result<Vec<u8>> {
+    let package = packages.first().context("missing root package")?;
+
+    let resolve = metadata
+        .resolve
+        .as_ref()
+        .context("missing dependency graph in cargo metadata")?;
+    let nodes = resolve
+        .nodes
+        .iter()
+        .map(|node| (&node.id, node))
+        .collect::<HashMap<_, _>>();
+    let all_packages = metadata
+        .packages
+        .iter()
+        .map(|pkg| (&pkg.id, pkg))
+        .collect::<HashMap<_, _>>();
+
+    let mut graph = BTreeMap::<&PackageId, BTreeSet<&PackageId>>::new();
+    let mut queue = packages.iter().map(|pkg| &pkg.id).collect::<VecDeque<_>>();
+
+    while let Some(id) = queue.pop_front() {
+        if graph.contains_key(id) {
+            continue;
+        }
+
+        let node = nodes
+            .get(id)
+            .with_context(|| format!("missing package `{id}` in dependency graph"))?;
+        let deps = node
+            .deps
+            .iter()
+            .filter(|dep| {
+                dep.dep_kinds
+                    .iter()
+                    .any(|info| info.kind == DependencyKind::Normal)
+            })
+            .map(|dep| &dep.pkg)
+            .collect::<BTreeSet<_>>();
+
+        queue.extend(deps.iter().copied());
+        graph.insert(id, deps);
+    }
+
+    let components = graph
+        .keys()
+        .filter(|id| **id != &package.id)
+        .map(|id| {
+            let ty = if packages.iter().any(|pkg| &pkg.id == *id) {
+                "application"
+            } else {
+                "library"
+            };
+
+            all_packages
+                .get(id)
+                .map(|pkg| Component::new(ty, pkg))
+                .with_context(|| format!("missing package `{id}` in cargo metadata"))
+        })
+        .collect::<Result<_>>()?;
+
+    let bom = Bom {
+        format: "CycloneDX",
+        spec_version: SPEC_VERSION,
+        version: 1,
+        metadata: BomMetadata {
+            timestamp,
+            tools: vec![Tool {
+                name: env!("CARGO_PKG_NAME"),
+                version: env!("CARGO_PKG_VERSION"),
+            }],
+            component: Component::new("application", package),
+        },
+        components,
+        dependencies: graph
+            .into_iter()
+            .map(|(id, deps)| Dependency {
+                reference: &id.repr,
+                depends_on: deps.into_iter().map(|dep| dep.repr.as_str()).collect(),
+            })
+            .collect(),
+    };
+
+    serde_json::to_vec_pretty(&bom).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use target_lexicon::Triple;
+
+    use super::*;
+    use crate::{features::Features, metadata};
+
+    #[test]
+    fn skip_dev_dependencies() -> Result<()> {
+        let metadata = metadata::load(None, &Triple::host(), &Features::default())?;
+        let bins = metadata::resolve_bins(&metadata, None, &[])?;
+
+        let bom = serde_json::from_slice::<Value>(&cyclonedx(
+            &metadata,
+            &[bins[0].package],
+            OffsetDateTime::from_unix_timestamp(1_700_000_000)?,
+        )?)?;
+        assert_eq!("2023-11-14T22:13:20Z", bom["metadata"]["timestamp"]);
+        let names = bom["components"]
+            .as_array()
+            .context("missing components")?
+            .iter()
+            .filter_map(|component| component["name"].as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!("build-assets", bom["metadata"]["component"]["name"]);
+        assert!(names.contains(&"cargo_metadata"));
+        assert!(!names.contains(&"build-assets"));
+        assert!(!names.contains(&"assert_fs"));
+
+        Ok(())
+    }
+}
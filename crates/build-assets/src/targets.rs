@@ -0,0 +1,363 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    fs,
+    str::FromStr,
+};
+
+use actions_common::glob;
+use anyhow::{ensure, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use globset::GlobSet;
+use serde::Deserialize;
+use target_lexicon::Triple;
+
+const BUILTIN: &str = include_str!("targets.toml");
+
+/// Cross-compilation settings for all targets matching a glob pattern.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TargetConfig {
+    pub pattern: String,
+    /// APT packages that provide the toolchain.
+    #[serde(default)]
+    pub apt: Vec<String>,
+    /// C compiler, for crates with C dependencies.
+    pub cc: Option<String>,
+    /// C++ compiler, for crates with C++ dependencies.
+    pub cxx: Option<String>,
+    /// Linker for the final binary.
+    pub linker: Option<String>,
+    /// Archiver, for crates that build static C libraries.
+    pub ar: Option<String>,
+    /// `objcopy` that understands the target's executables, to split off debug information.
+    pub objcopy: Option<String>,
+    /// Search path for pkg-config's `.pc` files of the target's libraries.
+    pub pkg_config_libdir: Option<String>,
+    /// Root directory of the target's headers and libraries, that pkg-config prefixes paths with.
+    pub sysroot: Option<Utf8PathBuf>,
+    /// Further environment variables to set during compilation.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct File {
+    #[serde(default)]
+    target: Vec<TargetConfig>,
+}
+
+/// Lookup table of [`TargetConfig`]s, combined from the built-in list and an optional user file.
+pub struct TargetTable {
+    entries: Vec<(GlobSet, TargetConfig)>,
+}
+
+impl TargetTable {
+    /// Load the built-in table. If a user file is given, its entries take precedence over the
+    /// built-in ones.
+    pub fn load(path: Option<&Utf8Path>) -> Result<Self> {
+        let mut configs = Vec::new();
+
+        if let Some(path) = path {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("failed reading target config `{path}`"))?;
+            configs.extend(
+                toml::from_str::<File>(&content)
+                    .with_context(|| format!("invalid target config `{path}`"))?
+                    .target,
+            );
+        }
+
+        configs.extend(toml::from_str::<File>(BUILTIN)?.target);
+
+        let entries = configs
+            .into_iter()
+            .map(|config| {
+                let set = glob::build_globset(&[&config.pattern])
+                    .with_context(|| format!("invalid target pattern `{}`", config.pattern))?;
+                Ok((set, config))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { entries })
+    }
+
+    /// Find the first entry that matches the target, if any.
+    #[must_use]
+    pub fn find(&self, target: &Triple) -> Option<&TargetConfig> {
+        let target = target.to_string();
+
+        self.entries
+            .iter()
+            .find(|(set, _)| set.is_match(&target))
+            .map(|(_, config)| config)
+    }
+}
+
+/// Single environment variable, in the form `<key>=<value>`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct EnvVar {
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for EnvVar {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .context("missing `=` between variable name and value")?;
+        let key = key.trim();
+
+        ensure!(!key.is_empty(), "missing variable name in `{s}`");
+
+        Ok(Self {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+}
+
+impl Display for EnvVar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::{prelude::*, NamedTempFile};
+
+    use super::*;
+
+    fn config(
+        pattern: &str,
+        apt: &[&str],
+        cc: Option<&str>,
+        linker: Option<&str>,
+        ar: Option<&str>,
+    ) -> TargetConfig {
+        TargetConfig {
+            pattern: pattern.to_owned(),
+            apt: apt.iter().map(|&pkg| pkg.to_owned()).collect(),
+            cc: cc.map(str::to_owned),
+            cxx: None,
+            linker: linker.map(str::to_owned),
+            ar: ar.map(str::to_owned),
+            objcopy: None,
+            pkg_config_libdir: None,
+            sysroot: None,
+            env: BTreeMap::new(),
+        }
+    }
+
+    fn gnu(pattern: &str, prefix: &str) -> TargetConfig {
+        let cc = format!("{prefix}-gcc");
+        TargetConfig {
+            cxx: Some(format!("{prefix}-g++")),
+            objcopy: Some(format!("{prefix}-objcopy")),
+            pkg_config_libdir: Some(format!(
+                "/usr/lib/{}/pkgconfig:/usr/share/pkgconfig",
+                prefix.replace("i686", "i386")
+            )),
+            ..config(
+                pattern,
+                &[&format!("gcc-{prefix}"), &format!("g++-{prefix}")],
+                Some(&cc),
+                Some(&cc),
+                Some(&format!("{prefix}-ar")),
+            )
+        }
+    }
+
+    fn lld(pattern: &str) -> TargetConfig {
+        TargetConfig {
+            objcopy: Some("llvm-objcopy".to_owned()),
+            ..config(pattern, &["llvm"], None, Some("rust-lld"), None)
+        }
+    }
+
+    /// Example targets for every built-in entry, together with the expected config.
+    fn expected() -> Vec<(&'static str, TargetConfig)> {
+        let none_eabi = TargetConfig {
+            cxx: Some("arm-none-eabi-g++".to_owned()),
+            objcopy: Some("arm-none-eabi-objcopy".to_owned()),
+            ..config(
+                "{arm,thumb}*-none-eabi*",
+                &["gcc-arm-none-eabi"],
+                Some("arm-none-eabi-gcc"),
+                None,
+                Some("arm-none-eabi-ar"),
+            )
+        };
+        let wasm = TargetConfig {
+            cxx: Some("clang++".to_owned()),
+            objcopy: Some("llvm-objcopy".to_owned()),
+            ..config(
+                "wasm32-*",
+                &["clang", "llvm"],
+                Some("clang"),
+                None,
+                Some("llvm-ar"),
+            )
+        };
+
+        vec![
+            (
+                "aarch64-unknown-linux-gnu",
+                gnu("aarch64-unknown-linux-gnu", "aarch64-linux-gnu"),
+            ),
+            (
+                "aarch64-unknown-linux-musl",
+                lld("aarch64-unknown-linux-musl"),
+            ),
+            (
+                "arm-unknown-linux-gnueabi",
+                gnu("arm*-unknown-linux-gnueabi", "arm-linux-gnueabi"),
+            ),
+            (
+                "armv7-unknown-linux-gnueabihf",
+                gnu("arm*-unknown-linux-gnueabihf", "arm-linux-gnueabihf"),
+            ),
+            (
+                "arm-unknown-linux-musleabi",
+                lld("arm*-unknown-linux-musleabi*"),
+            ),
+            (
+                "armv7-unknown-linux-musleabihf",
+                lld("arm*-unknown-linux-musleabi*"),
+            ),
+            ("armv7r-none-eabi", none_eabi),
+            (
+                "i686-unknown-linux-gnu",
+                gnu("i?86-unknown-linux-gnu", "i686-linux-gnu"),
+            ),
+            ("i686-unknown-linux-musl", lld("i?86-unknown-linux-musl")),
+            (
+                "x86_64-unknown-linux-musl",
+                config(
+                    "x86_64-unknown-linux-musl",
+                    &["musl-tools"],
+                    Some("musl-gcc"),
+                    Some("musl-gcc"),
+                    None,
+                ),
+            ),
+            (
+                "riscv64gc-unknown-linux-gnu",
+                gnu("riscv64gc-unknown-linux-gnu", "riscv64-linux-gnu"),
+            ),
+            (
+                "riscv64gc-unknown-linux-musl",
+                lld("riscv64gc-unknown-linux-musl"),
+            ),
+            (
+                "powerpc64le-unknown-linux-gnu",
+                gnu("powerpc64le-unknown-linux-gnu", "powerpc64le-linux-gnu"),
+            ),
+            (
+                "s390x-unknown-linux-gnu",
+                gnu("s390x-unknown-linux-gnu", "s390x-linux-gnu"),
+            ),
+            (
+                "mips-unknown-linux-gnu",
+                gnu("mips-unknown-linux-gnu", "mips-linux-gnu"),
+            ),
+            (
+                "mipsel-unknown-linux-gnu",
+                gnu("mipsel-unknown-linux-gnu", "mipsel-linux-gnu"),
+            ),
+            (
+                "mips64-unknown-linux-gnuabi64",
+                gnu("mips64-unknown-linux-gnuabi64", "mips64-linux-gnuabi64"),
+            ),
+            (
+                "mips64el-unknown-linux-gnuabi64",
+                gnu("mips64el-unknown-linux-gnuabi64", "mips64el-linux-gnuabi64"),
+            ),
+            ("wasm32-unknown-unknown", wasm),
+        ]
+    }
+
+    #[test]
+    fn builtin_entries() -> Result<()> {
+        let table = TargetTable::load(None)?;
+        let expected = expected();
+
+        for (target, config) in &expected {
+            assert_eq!(Some(config), table.find(&target.parse()?), "{target}");
+        }
+
+        for (_, config) in &table.entries {
+            assert!(
+                expected
+                    .iter()
+                    .any(|(_, expected)| expected.pattern == config.pattern),
+                "untested entry `{}`",
+                config.pattern
+            );
+        }
+
+        assert!(table.find(&"thumbv7em-none-eabihf".parse()?).is_some());
+        assert!(table.find(&"wasm32-wasip1".parse()?).is_some());
+        assert!(table.find(&"x86_64-unknown-linux-gnu".parse()?).is_none());
+        assert!(table.find(&"aarch64-apple-darwin".parse()?).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn user_entries() -> Result<()> {
+        let file = NamedTempFile::new("targets.toml")?;
+        file.write_str(
+            r#"
+            [[target]]
+            pattern = "aarch64-unknown-linux-musl"
+            apt = ["musl-cross-aarch64"]
+            cc = "aarch64-linux-musl-gcc"
+            linker = "aarch64-linux-musl-gcc"
+            objcopy = "aarch64-linux-musl-objcopy"
+            env = { CFLAGS_aarch64_unknown_linux_musl = "-O3" }
+            "#,
+        )?;
+
+        let table = TargetTable::load(Some(file.path().try_into()?))?;
+        let config = table
+            .find(&"aarch64-unknown-linux-musl".parse()?)
+            .context("missing entry")?;
+
+        assert_eq!(["musl-cross-aarch64"], config.apt.as_slice());
+        assert_eq!(
+            Some("aarch64-linux-musl-objcopy"),
+            config.objcopy.as_deref()
+        );
+        assert_eq!("-O3", config.env["CFLAGS_aarch64_unknown_linux_musl"]);
+        assert!(table.find(&"aarch64-unknown-linux-gnu".parse()?).is_some());
+
+        file.write_str("[[target]]\npattern = \"{]\"")?;
+        assert!(TargetTable::load(Some(file.path().try_into()?)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn env_var() -> Result<()> {
+        assert_eq!(
+            EnvVar {
+                key: "CFLAGS_aarch64_unknown_linux_gnu".to_owned(),
+                value: "-O2 -DNAME=value".to_owned(),
+            },
+            " CFLAGS_aarch64_unknown_linux_gnu=-O2 -DNAME=value".parse()?
+        );
+        assert_eq!(
+            "OPENSSL_STATIC=",
+            "OPENSSL_STATIC=".parse::<EnvVar>()?.to_string()
+        );
+
+        assert!("OPENSSL_STATIC".parse::<EnvVar>().is_err());
+        assert!("=1".parse::<EnvVar>().is_err());
+
+        Ok(())
+    }
+}
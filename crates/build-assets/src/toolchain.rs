@@ -1,23 +1,29 @@
 use std::{
+    cmp::Ordering,
     fmt::{self, Display},
+    fs,
     str::FromStr,
 };
 
-use anyhow::{anyhow, Context};
-use target_lexicon::Triple;
+use anyhow::{bail, ensure, Context, Result};
+use camino::Utf8Path;
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr};
+use target_lexicon::{Architecture, Environment, OperatingSystem, Triple, Vendor};
 use time::Date;
 
-#[derive(Debug, Default, PartialEq, Eq)]
+/// Rust toolchain, as named by rustup, in the form `<channel>[-<date>][-<host>]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct Toolchain {
     pub channel: Channel,
     pub date: Option<Date>,
-    pub host: Option<Triple>,
+    pub host: Option<Host>,
 }
 
 impl Toolchain {
     #[inline]
     #[must_use]
-    pub const fn new(channel: Channel, date: Option<Date>, host: Option<Triple>) -> Self {
+    pub const fn new(channel: Channel, date: Option<Date>, host: Option<Host>) -> Self {
         Self {
             channel,
             date,
@@ -51,7 +57,7 @@ impl Toolchain {
         Self {
             channel,
             date: None,
-            host: Some(host),
+            host: Some(Host::Full(host)),
         }
     }
 
@@ -61,51 +67,57 @@ impl Toolchain {
         Self {
             channel,
             date: Some(date),
-            host: Some(host),
+            host: Some(Host::Full(host)),
         }
     }
+
+    /// Toolchain that was linked into rustup under a custom name.
+    #[must_use]
+    pub fn custom(name: impl Into<String>) -> Self {
+        Self::channel(Channel::Custom(name.into()))
+    }
+
+    /// Parse the official rustup toolchain grammar, without considering custom names.
+    fn parse_official(s: &str) -> Result<Self> {
+        let (channel, s) = split_channel(s)?;
+        let s = s.strip_prefix('-').unwrap_or(s);
+
+        let (date, s) = if maybe_date(s) && (s.len() == 10 || s.as_bytes()[10] == b'-') {
+            let (date, s) = s.split_at(10);
+            let (year, rest) = date.split_once('-').context("missing date year")?;
+            let (month, day) = rest.split_once('-').context("missing date month and day")?;
+
+            let date = Date::from_calendar_date(
+                year.parse()?,
+                month.parse::<u8>()?.try_into()?,
+                day.parse()?,
+            )?;
+
+            (Some(date), s)
+        } else {
+            (None, s)
+        };
+
+        let s = s.strip_prefix('-').unwrap_or(s);
+
+        let host = (!s.is_empty()).then(|| s.parse()).transpose()?;
+
+        Ok(Self {
+            channel: channel.parse()?,
+            date,
+            host,
+        })
+    }
 }
 
 impl FromStr for Toolchain {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.split_once('-') {
-            Some((channel, s)) => {
-                let (date, s) = if maybe_date(s) {
-                    let (date, s) = s.split_at(10);
-                    let (year, rest) = date.split_once('-').context("missing date year")?;
-                    let (month, day) =
-                        rest.split_once('-').context("missing date month and day")?;
-
-                    let date = Date::from_calendar_date(
-                        year.parse()?,
-                        month.parse::<u8>()?.try_into()?,
-                        day.parse()?,
-                    )?;
-
-                    (Some(date), s)
-                } else {
-                    (None, s)
-                };
-
-                let s = s.strip_prefix('-').unwrap_or(s);
-
-                let host = (!s.is_empty())
-                    .then(|| s.parse().map_err(|e| anyhow!("{}", e)))
-                    .transpose()?;
-
-                Ok(Self {
-                    channel: channel.parse()?,
-                    date,
-                    host,
-                })
-            }
-            None => Ok(Self {
-                channel: s.parse()?,
-                date: None,
-                host: None,
-            }),
+        match Self::parse_official(s) {
+            Ok(toolchain) => Ok(toolchain),
+            Err(_) if is_custom_name(s) => Ok(Self::custom(s)),
+            Err(e) => Err(e),
         }
     }
 }
@@ -126,6 +138,83 @@ impl Display for Toolchain {
     }
 }
 
+/// Split the channel from the rest of a toolchain name. Versioned channels may include a beta
+/// pre-release suffix like `-beta.3`, which contains the separator as well.
+fn split_channel(s: &str) -> Result<(&str, &str)> {
+    for name in ["stable", "beta", "nightly"] {
+        if let Some(rest) = s.strip_prefix(name) {
+            if rest.is_empty() || rest.starts_with('-') {
+                return Ok(s.split_at(name.len()));
+            }
+        }
+    }
+
+    let mut end = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    ensure!(end > 0, "unknown channel in toolchain `{s}`");
+
+    if let Some(rest) = s[end..].strip_prefix("-beta") {
+        let number = rest.strip_prefix('.').map_or(0, |rest| {
+            1 + rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len())
+        });
+
+        if rest.len() == number || rest[number..].starts_with('-') {
+            end += "-beta".len() + number;
+        }
+    }
+
+    Ok(s.split_at(end))
+}
+
+/// Names that rustup accepts for custom toolchains, that were linked from a local directory.
+/// Names that were meant as official toolchain, but failed parsing, are excluded so that their
+/// error isn't hidden.
+fn is_custom_name(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with('-')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !looks_official(s)
+}
+
+/// Whether the name starts with a version number, or its first segment is a channel or one typo
+/// away from it, like `nighlty`. Typos in the first letter aren't considered, as these turn
+/// `beta` into real words like `meta` or `zeta`.
+fn looks_official(s: &str) -> bool {
+    let first = s.split('-').next().unwrap_or(s);
+
+    first.starts_with(|c: char| c.is_ascii_digit())
+        || ["stable", "beta", "nightly"]
+            .iter()
+            .any(|channel| first.get(..1) == channel.get(..1) && edit_distance(first, channel) <= 1)
+}
+
+/// Number of insertions, deletions, substitutions and swaps of adjacent characters, to turn one
+/// string into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+
+    rows[a.len()][b.len()]
+}
+
 fn maybe_date(s: &str) -> bool {
     if s.len() < 10 {
         return false;
@@ -144,13 +233,71 @@ fn is_digit(b: &[u8]) -> bool {
     b.iter().all(u8::is_ascii_digit)
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// Host triple of a toolchain, either complete or partially given like `msvc` or `i686-gnu`, in
+/// which case rustup fills in the rest from the current system.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Host {
+    Full(Triple),
+    Partial(String),
+}
+
+impl FromStr for Host {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(triple) = s.parse::<Triple>() {
+            if triple.to_string() == s {
+                return Ok(Self::Full(triple));
+            }
+        }
+
+        let valid = !s.is_empty()
+            && s.split('-').all(|part| {
+                Architecture::from_str(part).is_ok()
+                    || Vendor::from_str(part)
+                        .is_ok_and(|vendor| !matches!(vendor, Vendor::Custom(_)))
+                    || OperatingSystem::from_str(part).is_ok()
+                    || Environment::from_str(part).is_ok()
+            });
+        ensure!(valid, "invalid host triple `{s}`");
+
+        Ok(Self::Partial(s.to_owned()))
+    }
+}
+
+impl Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full(triple) => triple.fmt(f),
+            Self::Partial(s) => f.write_str(s),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub enum Channel {
+    #[default]
     Stable,
     Beta,
     Nightly,
-    Partial { major: u8, minor: u8 },
-    Full { major: u8, minor: u8, patch: u8 },
+    Partial {
+        major: u8,
+        minor: u8,
+    },
+    Full {
+        major: u8,
+        minor: u8,
+        patch: u8,
+    },
+    /// Beta pre-release of an upcoming version, like `1.80.0-beta.3` or `1.80-beta`.
+    PreRelease {
+        major: u8,
+        minor: u8,
+        patch: Option<u8>,
+        number: Option<u8>,
+    },
+    /// Name of a custom toolchain, that doesn't follow the official naming scheme.
+    Custom(String),
 }
 
 impl FromStr for Channel {
@@ -162,18 +309,36 @@ impl FromStr for Channel {
             "beta" => Self::Beta,
             "nightly" => Self::Nightly,
             _ => {
-                let mut parts = s.splitn(3, '.');
+                let (version, pre) = match s.split_once("-beta") {
+                    Some((version, pre)) => (version, Some(pre)),
+                    None => (s, None),
+                };
+
+                let mut parts = version.splitn(3, '.');
                 let major = parts.next().context("missing major version")?.parse()?;
                 let minor = parts.next().context("missing minor version")?.parse()?;
+                let patch = parts.next().map(str::parse).transpose()?;
 
-                if let Some(patch) = parts.next() {
-                    Self::Full {
+                match (patch, pre) {
+                    (Some(patch), None) => Self::Full {
                         major,
                         minor,
-                        patch: patch.parse()?,
-                    }
-                } else {
-                    Self::Partial { major, minor }
+                        patch,
+                    },
+                    (None, None) => Self::Partial { major, minor },
+                    (patch, Some(pre)) => Self::PreRelease {
+                        major,
+                        minor,
+                        patch,
+                        number: match pre {
+                            "" => None,
+                            _ => Some(
+                                pre.strip_prefix('.')
+                                    .context("missing `.` before beta number")?
+                                    .parse()?,
+                            ),
+                        },
+                    },
                 }
             }
         })
@@ -192,19 +357,138 @@ impl Display for Channel {
                 minor,
                 patch,
             } => write!(f, "{major}.{minor}.{patch}"),
+            Self::PreRelease {
+                major,
+                minor,
+                patch,
+                number,
+            } => {
+                write!(f, "{major}.{minor}")?;
+                if let Some(patch) = patch {
+                    write!(f, ".{patch}")?;
+                }
+                f.write_str("-beta")?;
+                if let Some(number) = number {
+                    write!(f, ".{number}")?;
+                }
+                Ok(())
+            }
+            Self::Custom(name) => f.write_str(name),
         }
     }
 }
 
-impl Default for Channel {
-    fn default() -> Self {
-        Self::Stable
+impl Channel {
+    /// Version that a numbered release channel points to. A partial version like `1.70` always
+    /// refers to the latest patch release, so it sorts after every full `1.70.x` version.
+    fn version(&self) -> Option<(u8, u8, u16)> {
+        match *self {
+            Self::Partial { major, minor } => Some((major, minor, u16::MAX)),
+            Self::Full {
+                major,
+                minor,
+                patch,
+            } => Some((major, minor, patch.into())),
+            _ => None,
+        }
+    }
+}
+
+/// Numbered release channels are ordered by their version, while all other channels can only
+/// be compared for equality, as the release they point to changes over time.
+impl PartialOrd for Channel {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.version(), other.version()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => (self == other).then_some(Ordering::Equal),
+        }
+    }
+}
+
+/// Toolchain settings pinned by a project, in the same way as rustup's
+/// [toolchain file](https://rust-lang.github.io/rustup/overrides.html#the-toolchain-file).
+#[serde_as]
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ToolchainFile {
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(rename = "channel", default)]
+    pub toolchain: Toolchain,
+    #[serde(default)]
+    pub components: Vec<String>,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub targets: Vec<Triple>,
+    pub profile: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ToolchainSection {
+    toolchain: ToolchainFile,
+}
+
+impl ToolchainFile {
+    /// Search the directory and all its parents for a `rust-toolchain` or `rust-toolchain.toml`
+    /// file, preferring the former if both exist, like rustup does.
+    pub fn discover(dir: &Utf8Path) -> Result<Option<Self>> {
+        for dir in dir.ancestors() {
+            for (name, legacy) in [("rust-toolchain", true), ("rust-toolchain.toml", false)] {
+                let path = dir.join(name);
+                if !path.is_file() {
+                    continue;
+                }
+
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("failed reading toolchain file `{path}`"))?;
+
+                return Self::parse(&content, legacy)
+                    .with_context(|| format!("invalid toolchain file `{path}`"))
+                    .map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Parse the file content. Legacy files can contain a plain toolchain name instead of the
+    /// TOML format.
+    fn parse(content: &str, legacy: bool) -> Result<Self> {
+        let content = content.trim();
+
+        if legacy && !content.contains('[') {
+            return Ok(Self {
+                toolchain: content.parse()?,
+                ..Self::default()
+            });
+        }
+
+        let file = toml::from_str::<toml::Table>(content)?;
+        if file
+            .get("toolchain")
+            .and_then(toml::Value::as_table)
+            .is_some_and(|toolchain| toolchain.contains_key("path"))
+        {
+            bail!("custom toolchains with a `path` are not supported");
+        }
+
+        Ok(file.try_into::<ToolchainSection>()?.toolchain)
+    }
+}
+
+impl From<Toolchain> for ToolchainFile {
+    fn from(toolchain: Toolchain) -> Self {
+        Self {
+            toolchain,
+            ..Self::default()
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
+    use assert_fs::{prelude::*, TempDir};
+    use proptest::{option, prelude::*, sample};
     use target_lexicon::{Architecture, BinaryFormat, Environment, OperatingSystem, Vendor};
     use time::macros::date;
 
@@ -282,4 +566,222 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn full_grammar() -> Result<()> {
+        assert_eq!(
+            Toolchain::channel(Channel::PreRelease {
+                major: 1,
+                minor: 80,
+                patch: Some(0),
+                number: Some(3),
+            }),
+            "1.80.0-beta.3".parse()?
+        );
+        assert_eq!(
+            Toolchain::with_host(
+                Channel::PreRelease {
+                    major: 1,
+                    minor: 80,
+                    patch: None,
+                    number: None,
+                },
+                "x86_64-pc-windows-gnu".parse()?
+            ),
+            "1.80-beta-x86_64-pc-windows-gnu".parse()?
+        );
+        assert_eq!(
+            Toolchain::with_date_and_host(
+                Channel::Beta,
+                date!(2024 - 05 - 01),
+                "x86_64-pc-windows-gnu".parse()?
+            ),
+            "beta-2024-05-01-x86_64-pc-windows-gnu".parse()?
+        );
+        assert_eq!(
+            Toolchain::new(
+                Channel::Nightly,
+                None,
+                Some(Host::Partial("i686-msvc".to_owned()))
+            ),
+            "nightly-i686-msvc".parse()?
+        );
+        assert_eq!(
+            Toolchain::custom("my-linked-toolchain"),
+            "my-linked-toolchain".parse()?
+        );
+        assert_eq!(Toolchain::custom("rust-dev"), "rust-dev".parse()?);
+        for name in ["meta", "zeta", "betamax", "betamax-dev"] {
+            assert_eq!(Toolchain::custom(name), name.parse()?);
+        }
+
+        assert!("".parse::<Toolchain>().is_err());
+        assert!("stable-custom".parse::<Toolchain>().is_err());
+        assert!("nighlty".parse::<Toolchain>().is_err());
+        assert!("btea-dev".parse::<Toolchain>().is_err());
+        assert!("nightly-2024-02-30".parse::<Toolchain>().is_err());
+        assert!("1.76.x".parse::<Toolchain>().is_err());
+        assert!("my toolchain".parse::<Toolchain>().is_err());
+
+        Ok(())
+    }
+
+    fn channel() -> impl Strategy<Value = Channel> {
+        prop_oneof![
+            Just(Channel::Stable),
+            Just(Channel::Beta),
+            Just(Channel::Nightly),
+            (any::<u8>(), any::<u8>()).prop_map(|(major, minor)| Channel::Partial { major, minor }),
+            (any::<u8>(), any::<u8>(), any::<u8>()).prop_map(|(major, minor, patch)| {
+                Channel::Full {
+                    major,
+                    minor,
+                    patch,
+                }
+            }),
+            (
+                any::<u8>(),
+                any::<u8>(),
+                option::of(any::<u8>()),
+                option::of(any::<u8>())
+            )
+                .prop_map(|(major, minor, patch, number)| Channel::PreRelease {
+                    major,
+                    minor,
+                    patch,
+                    number,
+                }),
+        ]
+    }
+
+    fn host() -> impl Strategy<Value = Host> {
+        prop_oneof![
+            sample::select(
+                &[
+                    "x86_64-unknown-linux-gnu",
+                    "x86_64-pc-windows-gnu",
+                    "i686-pc-windows-msvc",
+                    "aarch64-apple-darwin",
+                    "armv7-unknown-linux-gnueabihf",
+                    "riscv64gc-unknown-linux-gnu",
+                ][..]
+            )
+            .prop_map(|host| Host::Full(host.parse().unwrap())),
+            sample::select(&["msvc", "gnu", "x86_64", "i686-msvc", "aarch64-linux"][..])
+                .prop_map(|host| Host::Partial(host.to_owned())),
+        ]
+    }
+
+    fn toolchain() -> impl Strategy<Value = Toolchain> {
+        let date = (2015..2030_i32, 1..=12_u8, 1..=28_u8).prop_map(|(year, month, day)| {
+            Date::from_calendar_date(year, month.try_into().unwrap(), day).unwrap()
+        });
+        let official = (channel(), option::of(date), option::of(host()))
+            .prop_map(|(channel, date, host)| Toolchain::new(channel, date, host));
+        let custom = "[a-z][a-z0-9_.]{0,8}(-[a-z0-9_.]{1,8}){0,3}"
+            .prop_filter("official toolchain name", |name| is_custom_name(name))
+            .prop_map(Toolchain::custom);
+
+        prop_oneof![4 => official, 1 => custom]
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrip(toolchain in toolchain()) {
+            let parsed = toolchain.to_string().parse::<Toolchain>().unwrap();
+            prop_assert_eq!(toolchain, parsed);
+        }
+
+        #[test]
+        fn ordering(a in channel(), b in channel()) {
+            prop_assert_eq!(a == b, a.partial_cmp(&b) == Some(Ordering::Equal));
+            prop_assert_eq!(a.partial_cmp(&b).map(Ordering::reverse), b.partial_cmp(&a));
+        }
+    }
+
+    #[test]
+    fn channel_ordering() {
+        let full = |major, minor, patch| Channel::Full {
+            major,
+            minor,
+            patch,
+        };
+        let partial = |major, minor| Channel::Partial { major, minor };
+
+        assert!(full(1, 70, 0) < full(1, 70, 1));
+        assert!(full(1, 70, 9) < full(1, 71, 0));
+        assert!(full(1, 70, 9) < partial(1, 70));
+        assert!(partial(1, 70) < full(1, 71, 0));
+        assert!(partial(1, 9) < partial(1, 10));
+        assert!(full(2, 0, 0) > partial(1, 99));
+        assert_eq!(None, Channel::Stable.partial_cmp(&full(1, 70, 0)));
+        assert_eq!(None, Channel::Stable.partial_cmp(&Channel::Nightly));
+        assert_eq!(
+            Some(Ordering::Equal),
+            Channel::Beta.partial_cmp(&Channel::Beta)
+        );
+    }
+
+    #[test]
+    fn toolchain_file() -> Result<()> {
+        assert_eq!(
+            ToolchainFile {
+                toolchain: Toolchain::with_date(Channel::Nightly, date!(2024 - 02 - 01)),
+                components: vec!["rust-src".to_owned(), "clippy".to_owned()],
+                targets: vec!["wasm32-unknown-unknown".parse()?],
+                profile: Some("minimal".to_owned()),
+            },
+            ToolchainFile::parse(
+                r#"
+                [toolchain]
+                channel = "nightly-2024-02-01"
+                components = ["rust-src", "clippy"]
+                targets = ["wasm32-unknown-unknown"]
+                profile = "minimal"
+                "#,
+                false
+            )?
+        );
+        assert_eq!(
+            ToolchainFile::from(Toolchain::channel(Channel::Partial {
+                major: 1,
+                minor: 76
+            })),
+            ToolchainFile::parse("1.76\n", true)?
+        );
+        assert_eq!(
+            ToolchainFile::default(),
+            ToolchainFile::parse("[toolchain]\ncomponents = []", true)?
+        );
+
+        assert!(ToolchainFile::parse("1.76", false).is_err());
+        assert!(ToolchainFile::parse("[toolchain]\npath = \"/opt/rust\"", false).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn discover_toolchain_file() -> Result<()> {
+        let dir = TempDir::new()?;
+        let nested = dir.child("crates/app");
+        nested.create_dir_all()?;
+        let nested = Utf8Path::from_path(nested.path()).context("non-UTF-8 path")?;
+
+        assert_eq!(None, ToolchainFile::discover(nested)?);
+
+        dir.child("rust-toolchain.toml")
+            .write_str("[toolchain]\nchannel = \"beta\"")?;
+        assert_eq!(
+            Some(ToolchainFile::from(Toolchain::channel(Channel::Beta))),
+            ToolchainFile::discover(nested)?
+        );
+
+        dir.child("rust-toolchain").write_str("nightly")?;
+        assert_eq!(
+            Some(ToolchainFile::from(Toolchain::channel(Channel::Nightly))),
+            ToolchainFile::discover(nested)?
+        );
+
+        Ok(())
+    }
 }
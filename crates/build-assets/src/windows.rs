@@ -0,0 +1,350 @@
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use anyhow::{bail, Result};
+use cargo_metadata::Package;
+use heck::ToUpperCamelCase;
+use serde::Serialize;
+use target_lexicon::{Architecture, Environment, Triple};
+
+/// Version of the winget manifest schema that the manifests are written in.
+const WINGET_MANIFEST_VERSION: &str = "1.6.0";
+
+/// Application that is installed from one or more uploaded archives.
+pub struct App<'a> {
+    pub name: &'a str,
+    pub package: &'a Package,
+    pub archives: Vec<AppArchive<'a>>,
+}
+
+/// Uploaded zip archive for a single Windows target.
+pub struct AppArchive<'a> {
+    pub target: &'a Triple,
+    /// Download URL of the release asset.
+    pub url: &'a str,
+    /// Hex encoded SHA-256 digest of the archive.
+    pub sha256: String,
+    /// File names of the binaries, relative to the archive root.
+    pub bins: Vec<String>,
+    /// Top-level directory that contains all files, if the archive has one.
+    pub dir: Option<&'a str>,
+}
+
+impl AppArchive<'_> {
+    fn bin_paths(&self) -> impl Iterator<Item = String> + '_ {
+        self.bins.iter().map(|bin| match self.dir {
+            Some(dir) => format!("{dir}/{bin}"),
+            None => bin.clone(),
+        })
+    }
+}
+
+/// Ensure that manifests can describe the target's architecture.
+pub fn check_target(target: &Triple) -> Result<()> {
+    scoop_arch(target).map(|_| ())
+}
+
+/// Whether another of the given targets replaces this one in the manifests. They can only hold
+/// one archive per architecture, and MSVC builds are preferred over GNU ones.
+#[must_use]
+pub fn is_superseded(target: &Triple, targets: &[Triple]) -> bool {
+    target.environment != Environment::Msvc
+        && targets.iter().any(|other| {
+            other.environment == Environment::Msvc
+                && other.operating_system == target.operating_system
+                && other.architecture == target.architecture
+        })
+}
+
+/// Ensure that no two targets map to the same architecture in the manifests.
+pub fn check_archs<'a>(targets: impl IntoIterator<Item = &'a Triple>) -> Result<()> {
+    let mut seen = BTreeMap::new();
+
+    for target in targets {
+        let arch = scoop_arch(target)?;
+
+        if let Some(existing) = seen.insert(arch, target) {
+            bail!(
+                "both `{existing}` and `{target}` are {arch} targets, but Scoop and winget \
+                 manifests can only hold one archive per architecture"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn scoop_arch(target: &Triple) -> Result<&'static str> {
+    Ok(match target.architecture {
+        Architecture::X86_64 => "64bit",
+        Architecture::X86_32(_) => "32bit",
+        Architecture::Aarch64(_) => "arm64",
+        _ => bail!("no Windows package manager architecture known for target `{target}`"),
+    })
+}
+
+fn winget_arch(target: &Triple) -> Result<&'static str> {
+    Ok(match scoop_arch(target)? {
+        "64bit" => "x64",
+        "32bit" => "x86",
+        _ => "arm64",
+    })
+}
+
+#[derive(Serialize)]
+struct ScoopManifest<'a> {
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    homepage: Option<&'a str>,
+    license: &'a str,
+    architecture: BTreeMap<&'static str, ScoopArch<'a>>,
+}
+
+#[derive(Serialize)]
+struct ScoopArch<'a> {
+    url: &'a str,
+    hash: &'a str,
+    bin: Vec<String>,
+}
+
+/// Create a [Scoop] app manifest, which can be added to a bucket as `<name>.json`.
+///
+/// [Scoop]: https://scoop.sh
+pub fn scoop(app: &App<'_>) -> Result<(String, Vec<u8>)> {
+    check_archs(app.archives.iter().map(|archive| archive.target))?;
+
+    let package = app.package;
+    let manifest = ScoopManifest {
+        version: package.version.to_string(),
+        description: package.description.as_deref(),
+        homepage: homepage(package),
+        license: package.license.as_deref().unwrap_or("Unknown"),
+        architecture: app
+            .archives
+            .iter()
+            .map(|archive| {
+                Ok((
+                    scoop_arch(archive.target)?,
+                    ScoopArch {
+                        url: archive.url,
+                        hash: &archive.sha256,
+                        bin: archive.bin_paths().collect(),
+                    },
+                ))
+            })
+            .collect::<Result<_>>()?,
+    };
+
+    let mut data = serde_json::to_vec_pretty(&manifest)?;
+    data.push(b'\n');
+
+    Ok((format!("{}.json", app.name), data))
+}
+
+/// Create the version, default locale and installer manifests for [winget], which are submitted
+/// to the `winget-pkgs` repository together. The package identifier is formed from the publisher
+/// and the application name.
+///
+/// [winget]: https://learn.microsoft.com/windows/package-manager/
+pub fn winget(app: &App<'_>, publisher: &str) -> Result<Vec<(String, Vec<u8>)>> {
+    check_archs(app.archives.iter().map(|archive| archive.target))?;
+
+    let package = app.package;
+    let id = format!(
+        "{}.{}",
+        publisher.to_upper_camel_case(),
+        app.name.to_upper_camel_case()
+    );
+    let version = package.version.to_string();
+    let header = format!(
+        "PackageIdentifier: {}\nPackageVersion: {}\n",
+        quote(&id),
+        quote(&version)
+    );
+    let footer =
+        |ty: &str| format!("ManifestType: {ty}\nManifestVersion: {WINGET_MANIFEST_VERSION}\n");
+
+    if app.archives.is_empty() {
+        bail!("no Windows archives available for `{}`", app.name);
+    }
+
+    let mut installer = header.clone();
+    writeln!(installer, "InstallerType: zip")?;
+    writeln!(installer, "NestedInstallerType: portable")?;
+    writeln!(installer, "Installers:")?;
+    for archive in &app.archives {
+        writeln!(
+            installer,
+            "- Architecture: {}",
+            winget_arch(archive.target)?
+        )?;
+        writeln!(installer, "  InstallerUrl: {}", quote(archive.url))?;
+        writeln!(
+            installer,
+            "  InstallerSha256: {}",
+            archive.sha256.to_uppercase()
+        )?;
+        // Each archive may wrap its binaries in a directory named after its own target.
+        writeln!(installer, "  NestedInstallerFiles:")?;
+        for path in archive.bin_paths() {
+            let alias = path
+                .rsplit('/')
+                .next()
+                .unwrap_or(&path)
+                .trim_end_matches(".exe")
+                .to_owned();
+            writeln!(
+                installer,
+                "  - RelativeFilePath: {}",
+                quote(&path.replace('/', "\\"))
+            )?;
+            writeln!(installer, "    PortableCommandAlias: {}", quote(&alias))?;
+        }
+    }
+    installer.push_str(&footer("installer"));
+
+    let mut locale = header.clone();
+    writeln!(locale, "PackageLocale: en-US")?;
+    writeln!(locale, "Publisher: {}", quote(publisher))?;
+    writeln!(locale, "PackageName: {}", quote(app.name))?;
+    if let Some(homepage) = homepage(package) {
+        writeln!(locale, "PackageUrl: {}", quote(homepage))?;
+    }
+    writeln!(
+        locale,
+        "License: {}",
+        quote(package.license.as_deref().unwrap_or("Unknown"))
+    )?;
+    writeln!(
+        locale,
+        "ShortDescription: {}",
+        quote(
+            package
+                .description
+                .as_deref()
+                .and_then(|desc| desc.lines().next())
+                .unwrap_or(app.name)
+        )
+    )?;
+    locale.push_str(&footer("defaultLocale"));
+
+    let mut version_manifest = header;
+    writeln!(version_manifest, "DefaultLocale: en-US")?;
+    version_manifest.push_str(&footer("version"));
+
+    Ok(vec![
+        (format!("{id}.yaml"), version_manifest.into_bytes()),
+        (format!("{id}.locale.en-US.yaml"), locale.into_bytes()),
+        (format!("{id}.installer.yaml"), installer.into_bytes()),
+    ])
+}
+
+fn homepage(package: &Package) -> Option<&str> {
+    package
+        .homepage
+        .as_deref()
+        .or(package.repository.as_deref())
+}
+
+/// Quote a YAML value as double-quoted scalar, which shares its escaping rules with JSON strings.
+fn quote(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifests() -> Result<()> {
+        let package = serde_json::from_value::<Package>(serde_json::json!({
+            "name": "demo",
+            "version": "1.2.0",
+            "id": "demo 1.2.0 (path+file:///demo)",
+            "description": "Demo tool",
+            "license": "MIT",
+            "repository": "https://github.com/acme/demo",
+            "dependencies": [],
+            "targets": [],
+            "features": {},
+            "manifest_path": "/demo/Cargo.toml",
+        }))?;
+        let (x64, arm64) = (
+            "x86_64-pc-windows-msvc".parse()?,
+            "aarch64-pc-windows-msvc".parse()?,
+        );
+        let app = App {
+            name: "demo",
+            package: &package,
+            archives: vec![
+                AppArchive {
+                    target: &x64,
+                    url: "https://example.com/demo-x86_64-pc-windows-msvc.zip",
+                    sha256: "ab".repeat(32),
+                    bins: vec!["demo.exe".to_owned()],
+                    dir: None,
+                },
+                AppArchive {
+                    target: &arm64,
+                    url: "https://example.com/demo-aarch64-pc-windows-msvc.zip",
+                    sha256: "cd".repeat(32),
+                    bins: vec!["demo.exe".to_owned()],
+                    dir: Some("demo-aarch64-pc-windows-msvc"),
+                },
+            ],
+        };
+
+        let (name, data) = scoop(&app)?;
+        assert_eq!("demo.json", name);
+        let manifest = serde_json::from_slice::<serde_json::Value>(&data)?;
+        assert_eq!("1.2.0", manifest["version"]);
+        assert_eq!(
+            "https://example.com/demo-aarch64-pc-windows-msvc.zip",
+            manifest["architecture"]["arm64"]["url"]
+        );
+        assert_eq!("demo.exe", manifest["architecture"]["64bit"]["bin"][0]);
+
+        let files = winget(&app, "Acme Inc")?;
+        assert_eq!(
+            vec![
+                "AcmeInc.Demo.yaml",
+                "AcmeInc.Demo.locale.en-US.yaml",
+                "AcmeInc.Demo.installer.yaml",
+            ],
+            files
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+        );
+        let installer = String::from_utf8(files[2].1.clone())?;
+        assert!(installer.contains("  - RelativeFilePath: \"demo.exe\"\n"));
+        assert!(installer
+            .contains("  - RelativeFilePath: \"demo-aarch64-pc-windows-msvc\\\\demo.exe\"\n"));
+        assert!(installer.contains("    PortableCommandAlias: \"demo\""));
+        assert!(installer.contains(&format!("  InstallerSha256: {}", "CD".repeat(32))));
+
+        assert!(check_target(&"x86_64-pc-windows-gnu".parse()?).is_ok());
+        assert!(check_target(&"thumbv7a-pc-windows-msvc".parse()?).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn architectures() -> Result<()> {
+        let targets = [
+            "x86_64-pc-windows-msvc".parse()?,
+            "x86_64-pc-windows-gnu".parse()?,
+            "i686-pc-windows-gnu".parse()?,
+        ];
+
+        assert!(!is_superseded(&targets[0], &targets));
+        assert!(is_superseded(&targets[1], &targets));
+        assert!(!is_superseded(&targets[2], &targets));
+
+        assert!(check_archs([&targets[0], &targets[2]]).is_ok());
+        assert!(check_archs(&targets).is_err());
+
+        Ok(())
+    }
+}
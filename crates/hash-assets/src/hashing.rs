@@ -13,11 +13,28 @@ use sha2::{Digest, Sha256, Sha512};
 use tracing::info;
 
 /// Hash the given list of assets with multiple hashing algorithms.
-///
-/// A list of hash files is returned, one for each algorithm containing the hashes of all given
-/// files.
-pub fn hash(files: Vec<(&Asset, AssetReader)>) -> Result<Vec<(String, Vec<u8>)>> {
-    build_files(&build_hashes(files)?)
+pub fn hash(files: Vec<(&Asset, AssetReader)>) -> Result<Checksums<'_>> {
+    build_hashes(files).map(|hashes| Checksums { hashes })
+}
+
+/// Hashes of a list of assets.
+pub struct Checksums<'a> {
+    hashes: Vec<(&'a Asset, Hashes)>,
+}
+
+impl<'a> Checksums<'a> {
+    /// Build a list of hash files, one for each algorithm containing the hashes of all files.
+    pub fn files(&self) -> Result<Vec<(String, Vec<u8>)>> {
+        build_files(&self.hashes)
+    }
+
+    /// Get the hex encoded SHA-256 hash of each asset.
+    pub fn sha256(&self) -> impl Iterator<Item = (&'a Asset, String)> + '_ {
+        self.hashes.iter().filter_map(|(asset, hashes)| {
+            let hash = hashes.values.get(&Hash::Sha256)?;
+            Some((*asset, hex::encode(hash)))
+        })
+    }
 }
 
 struct Hashes {
@@ -93,7 +110,9 @@ impl Hashes {
                 });
             }
 
-            hashes.values_mut().for_each(|hasher| hasher.update(buffer));
+            for hasher in hashes.values_mut() {
+                hasher.update(buffer);
+            }
 
             let length = buffer.len();
             input.consume(length);
@@ -138,7 +157,7 @@ fn write_hashes(
 
     for (asset, hashes) in files {
         if let Some(hash) = f(hashes) {
-            writeln!(&mut checksums, "{} *{}", hex::encode(hash), asset.name,)?;
+            writeln!(&mut checksums, "{} *{}", hex::encode(hash), asset.name)?;
         }
     }
 
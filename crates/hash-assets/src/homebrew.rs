@@ -0,0 +1,311 @@
+//! Generation of Homebrew formulas that install released archives.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{bail, ensure, Result};
+use heck::ToUpperCamelCase;
+use target_lexicon::{Architecture, Environment, OperatingSystem, Triple};
+use tracing::info;
+
+/// Description of a formula, apart from its archives.
+pub struct Formula<'a> {
+    /// Name of the formula, which is also the name of the `.rb` file.
+    pub name: &'a str,
+    /// Version of the released software.
+    pub version: &'a str,
+    /// Short description of the software.
+    pub desc: Option<&'a str>,
+    /// Website of the software.
+    pub homepage: &'a str,
+    /// SPDX license expression.
+    pub license: Option<&'a str>,
+    /// Names of the binaries to install from the archives.
+    pub bins: &'a [String],
+}
+
+/// Released archive that the formula can download.
+pub struct Archive<'a> {
+    /// File name of the asset, which must contain the target triple.
+    pub name: &'a str,
+    /// Download URL of the asset.
+    pub url: &'a str,
+    /// Hex encoded SHA-256 hash of the asset.
+    pub sha256: &'a str,
+}
+
+/// File extensions of archives that Homebrew can unpack.
+const ARCHIVE_EXTENSIONS: &[&str] = &[".tar.gz", ".tar.xz", ".tar.zst", ".tar.bz2", ".zip"];
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Os {
+    MacOs,
+    Linux,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Cpu {
+    Arm,
+    Intel,
+}
+
+impl Os {
+    fn block(self) -> &'static str {
+        match self {
+            Self::MacOs => "on_macos",
+            Self::Linux => "on_linux",
+        }
+    }
+}
+
+impl Cpu {
+    fn block(self) -> &'static str {
+        match self {
+            Self::Arm => "on_arm",
+            Self::Intel => "on_intel",
+        }
+    }
+}
+
+/// Render the formula as Ruby file, with a download for each operating system and CPU that one
+/// of the archives is built for. Other files like signatures, archives for other targets, without
+/// a target triple in their name, or with debug symbols as created by build-assets, are ignored.
+/// If there are both musl and glibc archives for the same Linux platform, the musl one is used.
+pub fn render(formula: &Formula<'_>, archives: &[Archive<'_>]) -> Result<(String, Vec<u8>)> {
+    let mut downloads = BTreeMap::new();
+
+    for archive in archives {
+        let Some(stem) = ARCHIVE_EXTENSIONS
+            .iter()
+            .find_map(|extension| archive.name.strip_suffix(extension))
+        else {
+            continue;
+        };
+
+        if stem.ends_with("-debug") {
+            continue;
+        }
+
+        let Some(target) = find_triple(stem) else {
+            continue;
+        };
+        let Some((os, cpu)) = platform(&target) else {
+            continue;
+        };
+        let musl = target.environment == Environment::Musl;
+
+        if let Some((existing, existing_musl)) = downloads.insert((os, cpu), (archive, musl)) {
+            // Statically linked musl binaries run on any Linux, unlike ones built against glibc.
+            if existing_musl != musl {
+                if existing_musl {
+                    downloads.insert((os, cpu), (existing, existing_musl));
+                }
+                continue;
+            }
+
+            bail!(
+                "both `{}` and `{}` match {} {}, only one archive per platform can be downloaded",
+                existing.name,
+                archive.name,
+                os.block(),
+                cpu.block()
+            );
+        }
+    }
+
+    ensure!(
+        !downloads.is_empty(),
+        "none of the assets is built for macOS or Linux"
+    );
+
+    let mut rb = String::new();
+    writeln!(rb, "class {} < Formula", formula.name.to_upper_camel_case())?;
+    if let Some(desc) = formula.desc {
+        writeln!(rb, "  desc {}", quote(desc))?;
+    }
+    writeln!(rb, "  homepage {}", quote(formula.homepage))?;
+    writeln!(rb, "  version {}", quote(formula.version))?;
+    if let Some(license) = formula.license {
+        writeln!(rb, "  license {}", quote(license))?;
+    }
+
+    let mut last_os = None;
+
+    for ((os, cpu), (archive, _)) in &downloads {
+        if last_os != Some(os) {
+            if last_os.is_some() {
+                writeln!(rb, "  end")?;
+            }
+            writeln!(rb, "\n  {} do", os.block())?;
+            last_os = Some(os);
+        }
+
+        writeln!(rb, "    {} do", cpu.block())?;
+        writeln!(rb, "      url {}", quote(archive.url))?;
+        writeln!(rb, "      sha256 {}", quote(archive.sha256))?;
+        writeln!(rb, "    end")?;
+    }
+
+    writeln!(rb, "  end")?;
+    writeln!(rb, "\n  def install")?;
+    for bin in formula.bins {
+        writeln!(rb, "    bin.install {}", quote(bin))?;
+    }
+    writeln!(rb, "  end")?;
+    writeln!(rb, "end")?;
+
+    let name = format!("{}.rb", formula.name);
+    info!(%name, archives = downloads.len(), "rendered Homebrew formula");
+
+    Ok((name, rb.into_bytes()))
+}
+
+/// Find the target triple in an archive name without extension, like
+/// `demo-1.0.0-aarch64-apple-darwin`, by trying all dash-separated parts for the longest one that
+/// parses as a triple.
+fn find_triple(name: &str) -> Option<Triple> {
+    let parts = name.split('-').collect::<Vec<_>>();
+
+    (0..parts.len()).find_map(|start| {
+        (start + 2..=parts.len())
+            .rev()
+            .find_map(|end| parts[start..end].join("-").parse::<Triple>().ok())
+            .filter(|triple| triple.operating_system != OperatingSystem::Unknown)
+    })
+}
+
+fn platform(target: &Triple) -> Option<(Os, Cpu)> {
+    let os = match target.operating_system {
+        OperatingSystem::Darwin | OperatingSystem::MacOSX { .. } => Os::MacOs,
+        OperatingSystem::Linux => Os::Linux,
+        _ => return None,
+    };
+    let cpu = match target.architecture {
+        Architecture::Aarch64(_) => Cpu::Arm,
+        Architecture::X86_64 => Cpu::Intel,
+        _ => return None,
+    };
+
+    Some((os, cpu))
+}
+
+/// Quote a string as Ruby literal, without interpolation.
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' | '\\' | '#' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_formula() -> Result<()> {
+        let bins = ["demo".to_owned()];
+        let formula = Formula {
+            name: "demo-tool",
+            version: "1.2.0",
+            desc: Some("Demo \"tool\""),
+            homepage: "https://github.com/acme/demo",
+            license: Some("MIT"),
+            bins: &bins,
+        };
+        let archive = |name| Archive {
+            name,
+            url: name,
+            sha256: "00",
+        };
+
+        let (name, data) = render(
+            &formula,
+            &[
+                archive("demo-1.2.0-x86_64-apple-darwin.tar.gz"),
+                archive("demo-1.2.0-aarch64-apple-darwin.tar.gz"),
+                archive("demo-1.2.0-x86_64-unknown-linux-musl.tar.gz"),
+                archive("demo-1.2.0-x86_64-unknown-linux-musl.tar.gz.cdx.json"),
+                archive("demo-1.2.0-x86_64-unknown-linux-musl.tar.gz.intoto.jsonl"),
+                archive("demo-1.2.0-x86_64-unknown-linux-musl.tar.gz.asc"),
+                archive("demo-1.2.0-x86_64-unknown-linux-musl-debug.tar.gz"),
+                archive("demo-1.2.0-aarch64-unknown-linux-gnu"),
+                archive("demo-1.2.0-x86_64-pc-windows-msvc.zip"),
+                archive("checksums.sha256"),
+            ],
+        )?;
+        assert_eq!("demo-tool.rb", name);
+
+        let rb = String::from_utf8(data)?;
+        assert_eq!(
+            vec![
+                "class DemoTool < Formula",
+                "  desc \"Demo \\\"tool\\\"\"",
+                "  homepage \"https://github.com/acme/demo\"",
+                "  version \"1.2.0\"",
+                "  license \"MIT\"",
+                "",
+                "  on_macos do",
+                "    on_arm do",
+                "      url \"demo-1.2.0-aarch64-apple-darwin.tar.gz\"",
+                "      sha256 \"00\"",
+                "    end",
+                "    on_intel do",
+                "      url \"demo-1.2.0-x86_64-apple-darwin.tar.gz\"",
+                "      sha256 \"00\"",
+                "    end",
+                "  end",
+                "",
+                "  on_linux do",
+                "    on_intel do",
+                "      url \"demo-1.2.0-x86_64-unknown-linux-musl.tar.gz\"",
+                "      sha256 \"00\"",
+                "    end",
+                "  end",
+                "",
+                "  def install",
+                "    bin.install \"demo\"",
+                "  end",
+                "end",
+            ],
+            rb.lines().collect::<Vec<_>>()
+        );
+
+        for archives in [
+            [
+                archive("demo-x86_64-unknown-linux-gnu.tar.gz"),
+                archive("demo-x86_64-unknown-linux-musl.tar.gz"),
+            ],
+            [
+                archive("demo-x86_64-unknown-linux-musl.tar.gz"),
+                archive("demo-x86_64-unknown-linux-gnu.tar.gz"),
+            ],
+        ] {
+            let (_, data) = render(&formula, &archives)?;
+            let rb = String::from_utf8(data)?;
+            assert!(rb.contains("url \"demo-x86_64-unknown-linux-musl.tar.gz\""));
+            assert!(!rb.contains("linux-gnu"));
+        }
+        assert!(render(
+            &formula,
+            &[
+                archive("demo-x86_64-unknown-linux-gnu.tar.gz"),
+                archive("demo-x86_64-unknown-linux-gnu.tar.xz"),
+            ],
+        )
+        .is_err());
+        assert!(render(&formula, &[archive("demo-x86_64-pc-windows-msvc.zip")]).is_err());
+
+        Ok(())
+    }
+}
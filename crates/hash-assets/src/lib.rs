@@ -5,3 +5,4 @@
 #![allow(clippy::missing_errors_doc)]
 
 pub mod hashing;
+pub mod homebrew;
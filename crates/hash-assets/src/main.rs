@@ -2,12 +2,18 @@
 #![deny(rust_2018_idioms, clippy::all, clippy::pedantic)]
 #![allow(clippy::missing_errors_doc)]
 
+use std::{fs, path::PathBuf};
+
 use actions_common::{
-    env, glob,
+    env::{self, GithubArgs},
+    glob,
     http::{self, Release},
 };
 use anyhow::{Context, Result};
-use hash_assets::hashing;
+use hash_assets::{
+    hashing::{self, Checksums},
+    homebrew::{self, Archive, Formula},
+};
 use rayon::prelude::*;
 use serde::Deserialize;
 use serde_with::{formats::CommaSeparator, serde_as, StringWithSeparator};
@@ -19,6 +25,14 @@ struct Opt {
     #[serde_as(as = "StringWithSeparator::<CommaSeparator, String>")]
     #[serde(default = "default_globs")]
     globs: Vec<String>,
+    homebrew_formula: Option<String>,
+    homebrew_desc: Option<String>,
+    homebrew_homepage: Option<String>,
+    homebrew_license: Option<String>,
+    #[serde_as(as = "StringWithSeparator::<CommaSeparator, String>")]
+    #[serde(default)]
+    homebrew_bins: Vec<String>,
+    homebrew_path: Option<PathBuf>,
 }
 
 fn default_globs() -> Vec<String> {
@@ -50,13 +64,68 @@ fn main() -> Result<()> {
         .collect::<Result<Vec<_>>>()
         .context("failed downloading assets")?;
 
-    let hashes = hashing::hash(assets).context("failed hashing assets")?;
+    let checksums = hashing::hash(assets).context("failed hashing assets")?;
+    let mut files = checksums.files()?;
+
+    if let Some(name) = &opt.homebrew_formula {
+        let (file, data) = formula(&opt, &github, name, &checksums)
+            .context("failed rendering Homebrew formula")?;
+
+        if let Some(path) = &opt.homebrew_path {
+            fs::write(path, data)
+                .with_context(|| format!("failed writing `{}`", path.display()))?;
+            info!(path = %path.display(), "wrote Homebrew formula");
+        } else {
+            files.push((file, data));
+        }
+    }
 
-    upload_files(&github.token, &github.repository, &release, &hashes)?;
+    upload_files(&github.token, &github.repository, &release, &files)?;
 
     Ok(())
 }
 
+/// Render a Homebrew formula for the hashed archives. The version is taken from the tag, and the
+/// formula installs a binary of the same name unless others are given.
+fn formula(
+    opt: &Opt,
+    github: &GithubArgs,
+    name: &str,
+    checksums: &Checksums<'_>,
+) -> Result<(String, Vec<u8>)> {
+    let homepage = opt
+        .homebrew_homepage
+        .clone()
+        .unwrap_or_else(|| format!("{}/{}", github.server_url, github.repository));
+    let bins = if opt.homebrew_bins.is_empty() {
+        vec![name.to_owned()]
+    } else {
+        opt.homebrew_bins.clone()
+    };
+
+    let hashes = checksums.sha256().collect::<Vec<_>>();
+    let archives = hashes
+        .iter()
+        .map(|(asset, sha256)| Archive {
+            name: &asset.name,
+            url: &asset.browser_download_url,
+            sha256,
+        })
+        .collect::<Vec<_>>();
+
+    homebrew::render(
+        &Formula {
+            name,
+            version: github.ref_name.trim_start_matches('v'),
+            desc: opt.homebrew_desc.as_deref(),
+            homepage: &homepage,
+            license: opt.homebrew_license.as_deref(),
+            bins: &bins,
+        },
+        &archives,
+    )
+}
+
 fn upload_files(
     token: &str,
     repo: &str,
@@ -1,13 +1,12 @@
 //! Calling GPG to manage keys and sign files.
 
 use std::{
-    io::{self, BufRead, Cursor, Write},
+    io::{self, BufRead, Cursor, Read, Write},
     path::{Path, PathBuf},
     process::{Command, Stdio},
 };
 
-use actions_common::http::{Asset, AssetReader};
-use anyhow::{bail, ensure, Result};
+use anyhow::{bail, ensure, Context, Result};
 use once_cell::sync::OnceCell;
 use rayon::prelude::*;
 use tracing::info;
@@ -98,21 +97,71 @@ pub fn delete_key(key_id: &KeyId) -> Result<()> {
     Ok(())
 }
 
+/// Export the public part of the given key in ASCII armored form.
+pub fn export_key(key_id: &KeyId) -> Result<String> {
+    let gpg = find_gpg()?;
+
+    let output = Command::new(gpg)
+        .arg("--export")
+        .args(DEFAULT_ARGS)
+        .arg("--armor")
+        .arg(&key_id.0)
+        .output()?;
+
+    ensure!(
+        output.status.success(),
+        "failed exporting public key: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout).map_err(Into::into)
+}
+
+/// Get the fingerprint and public key algorithm of the given key.
+///
+/// The algorithm is the numeric identifier as defined in
+/// [RFC 9580](https://www.rfc-editor.org/rfc/rfc9580.html#name-public-key-algorithms).
+pub fn key_details(key_id: &KeyId) -> Result<(String, u8)> {
+    let gpg = find_gpg()?;
+
+    let output = Command::new(gpg)
+        .arg("--list-keys")
+        .args(DEFAULT_ARGS)
+        .arg(&key_id.0)
+        .output()?;
+
+    ensure!(
+        output.status.success(),
+        "failed listing public key: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let lines = Cursor::new(output.stdout).lines();
+    for line in lines {
+        if let Some(line) = line?.strip_prefix("pub:") {
+            let algorithm = line.split(':').nth(2).context("missing key algorithm")?;
+            return Ok((key_id.0.clone(), algorithm.parse()?));
+        }
+    }
+
+    bail!("failed finding public key")
+}
+
 /// Sign the list of given file contents with GPG.
 ///
 /// The files are a tuple of file name and content. The file name is mostly used for error reporting
 /// and logging, but to generate a name for the signature as well.
 #[allow(clippy::missing_panics_doc)]
-pub fn sign(
+pub fn sign<R: Read + Send>(
     key_id: &KeyId,
     passphrase: Option<&str>,
-    files: Vec<(&Asset, AssetReader)>,
+    files: Vec<(&str, R)>,
 ) -> Result<Vec<(String, Vec<u8>)>> {
     let gpg = find_gpg()?;
 
     files
         .into_par_iter()
-        .map(|(asset, mut reader)| {
+        .map(|(name, mut reader)| {
             let mut cmd = Command::new(gpg);
             cmd.stdin(Stdio::piped())
                 .stdout(Stdio::piped())
@@ -136,13 +185,13 @@ pub fn sign(
             ensure!(
                 output.status.success(),
                 "failed creating signature for {:?}: {}",
-                asset.name,
+                name,
                 String::from_utf8_lossy(&output.stderr)
             );
 
-            info!(name = %asset.name, "signed file");
+            info!(%name, "signed file");
 
-            Ok((format!("{}.asc", asset.name), output.stdout))
+            Ok((format!("{name}.asc"), output.stdout))
         })
         .collect()
 }
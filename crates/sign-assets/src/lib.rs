@@ -1 +1,4 @@
 pub mod gpg;
+pub mod manifest;
+pub mod native;
+pub mod signer;
@@ -10,7 +10,11 @@ use anyhow::{Context, Result};
 use rayon::prelude::*;
 use serde::Deserialize;
 use serde_with::{formats::CommaSeparator, serde_as, StringWithSeparator};
-use sign_assets::gpg;
+use sign_assets::{
+    manifest,
+    signer::{Backend, Signer},
+};
+use time::OffsetDateTime;
 use tracing::info;
 
 #[serde_as]
@@ -18,9 +22,13 @@ use tracing::info;
 struct Opt {
     gpg_key: String,
     gpg_passphrase: Option<String>,
+    #[serde(default)]
+    backend: Backend,
     #[serde_as(as = "StringWithSeparator::<CommaSeparator, String>")]
     #[serde(default = "default_globs")]
     globs: Vec<String>,
+    #[serde(default)]
+    publish_key: bool,
 }
 
 fn default_globs() -> Vec<String> {
@@ -52,10 +60,26 @@ fn main() -> Result<()> {
         .collect::<Result<Vec<_>>>()
         .context("failed downloading assets")?;
 
-    let key_id = gpg::import_key(&opt.gpg_key, opt.gpg_passphrase.as_deref())?;
-    let signatures = gpg::sign(&key_id, opt.gpg_passphrase.as_deref(), assets);
+    let signer = Signer::import(opt.backend, &opt.gpg_key, opt.gpg_passphrase.as_deref())?;
+    let signed_at = OffsetDateTime::now_utc();
+    let signatures = signer
+        .sign(
+            assets
+                .into_iter()
+                .map(|(asset, reader)| (asset.name.as_str(), reader))
+                .collect(),
+        )
+        .and_then(|mut signatures| {
+            if opt.publish_key {
+                let files = manifest::build(&signer, signed_at, &signatures)?;
+                info!("built public key and signing manifest");
+                signatures.extend(files);
+            }
+
+            Ok(signatures)
+        });
 
-    gpg::delete_key(&key_id)?;
+    signer.close()?;
 
     upload_files(&github.token, &github.repository, &release, &signatures?)?;
 
@@ -0,0 +1,122 @@
+//! Publishing of the signing key and a manifest, to verify signatures without a keyserver.
+
+use anyhow::Result;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::signer::Signer;
+
+/// File name of the exported public key.
+pub const KEY_FILE: &str = "signing-key.asc";
+/// File name of the signing manifest.
+pub const MANIFEST_FILE: &str = "signing-manifest.json";
+
+/// Details about a single signing run, stored in JSON format next to the signatures.
+#[derive(Serialize)]
+pub struct Manifest<'a> {
+    /// Fingerprint of the primary key in upper case hex encoding.
+    pub fingerprint: &'a str,
+    /// Human-readable name of the primary key's algorithm.
+    pub algorithm: &'static str,
+    /// Point in time when the signatures were created.
+    #[serde(with = "time::serde::rfc3339")]
+    pub signed_at: OffsetDateTime,
+    /// File name of the public key.
+    pub key: &'a str,
+    /// All assets that were signed, sorted by name.
+    pub assets: Vec<SignedAsset<'a>>,
+}
+
+/// Single entry of the [`Manifest`], linking a file to its signature.
+#[derive(Serialize)]
+pub struct SignedAsset<'a> {
+    /// File name of the signed asset.
+    pub name: &'a str,
+    /// File name of the detached signature.
+    pub signature: &'a str,
+}
+
+/// Create the exported public key and the manifest describing the given signatures.
+///
+/// The signatures are the ones returned from [`Signer::sign`] and are expected to carry the signed
+/// file's name plus an `.asc` extension.
+pub fn build(
+    signer: &Signer<'_>,
+    signed_at: OffsetDateTime,
+    signatures: &[(String, Vec<u8>)],
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let (fingerprint, algorithm) = signer.key_details()?;
+    let public_key = signer.export_key()?;
+
+    let mut assets = signatures
+        .iter()
+        .map(|(signature, _)| SignedAsset {
+            name: signature.strip_suffix(".asc").unwrap_or(signature),
+            signature,
+        })
+        .collect::<Vec<_>>();
+    assets.sort_unstable_by_key(|asset| asset.name);
+
+    let manifest = Manifest {
+        fingerprint: &fingerprint,
+        algorithm: algorithm_name(algorithm),
+        signed_at,
+        key: KEY_FILE,
+        assets,
+    };
+
+    Ok(vec![
+        (KEY_FILE.to_owned(), public_key.into_bytes()),
+        (
+            MANIFEST_FILE.to_owned(),
+            serde_json::to_vec_pretty(&manifest)?,
+        ),
+    ])
+}
+
+/// Get the name for a public key algorithm, identified by its numeric ID as defined in
+/// [RFC 9580](https://www.rfc-editor.org/rfc/rfc9580.html#name-public-key-algorithms).
+#[must_use]
+pub fn algorithm_name(id: u8) -> &'static str {
+    match id {
+        1..=3 => "RSA",
+        16 | 20 => "ElGamal",
+        17 => "DSA",
+        18 => "ECDH",
+        19 => "ECDSA",
+        22 => "EdDSA",
+        25 => "X25519",
+        26 => "X448",
+        27 => "Ed25519",
+        28 => "Ed448",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn serialize_manifest() -> Result<()> {
+        let manifest = Manifest {
+            fingerprint: "24B536EEDC7D1FBCFC678E786C63F836857F5C34",
+            algorithm: algorithm_name(1),
+            signed_at: datetime!(2024-03-01 12:00 UTC),
+            key: KEY_FILE,
+            assets: vec![SignedAsset {
+                name: "checksums.b2",
+                signature: "checksums.b2.asc",
+            }],
+        };
+
+        assert_eq!(
+            r#"{"fingerprint":"24B536EEDC7D1FBCFC678E786C63F836857F5C34","algorithm":"RSA","signed_at":"2024-03-01T12:00:00Z","key":"signing-key.asc","assets":[{"name":"checksums.b2","signature":"checksums.b2.asc"}]}"#,
+            serde_json::to_string(&manifest)?
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+//! Pure-Rust OpenPGP implementation to sign files without a system-installed GPG.
+
+use std::io::Read;
+
+use anyhow::{Context, Result};
+use pgp::{
+    composed::{ArmorOptions, Deserializable, DetachedSignature, SignedSecretKey},
+    crypto::hash::HashAlgorithm,
+    packet::SecretSubkey,
+    types::{KeyDetails, Password, SigningKey},
+};
+use rayon::prelude::*;
+use tracing::info;
+
+/// Hash algorithm used for all signatures, matching the default of recent GPG versions.
+const HASH_ALGORITHM: HashAlgorithm = HashAlgorithm::Sha256;
+
+/// Secret key that was parsed and unlocked with [`import_key`].
+pub struct Key {
+    key: SignedSecretKey,
+    passphrase: Password,
+}
+
+impl Key {
+    /// Select the subkey that creates signatures. Like GPG, the last subkey that is capable of
+    /// signing is preferred. If there is none, the primary key should be used instead.
+    fn signing_subkey(&self) -> Option<&SecretSubkey> {
+        self.key
+            .secret_subkeys
+            .iter()
+            .rev()
+            .find(|subkey| subkey.signatures.iter().any(|sig| sig.key_flags().sign()))
+            .map(|subkey| &subkey.key)
+    }
+}
+
+/// Parse an ASCII armored secret key and make sure it can be unlocked with the given passphrase.
+pub fn import_key(key: &str, passphrase: Option<&str>) -> Result<Key> {
+    let (key, _) = SignedSecretKey::from_string(key).context("failed parsing secret key")?;
+    key.verify_bindings().context("invalid secret key")?;
+
+    let key = Key {
+        key,
+        passphrase: passphrase.map_or_else(Password::empty, Password::from),
+    };
+
+    match key.signing_subkey() {
+        Some(subkey) => subkey.unlock(&key.passphrase, |_, _| Ok(())),
+        None => key.key.primary_key.unlock(&key.passphrase, |_, _| Ok(())),
+    }
+    .context("failed unlocking secret key")??;
+
+    info!(id = %format!("{:X}", key.key.fingerprint()), "imported OpenPGP key");
+
+    Ok(key)
+}
+
+/// Export the public part of the given key in ASCII armored form.
+pub fn export_key(key: &Key) -> Result<String> {
+    key.key
+        .to_public_key()
+        .to_armored_string(ArmorOptions::default())
+        .context("failed exporting public key")
+}
+
+/// Get the fingerprint and public key algorithm of the given key, in the same form as
+/// [`crate::gpg::key_details`].
+#[must_use]
+pub fn key_details(key: &Key) -> (String, u8) {
+    (
+        format!("{:X}", key.key.fingerprint()),
+        key.key.algorithm().into(),
+    )
+}
+
+/// Sign the list of given file contents with the native OpenPGP implementation.
+///
+/// The files are a tuple of file name and content, the same as for [`crate::gpg::sign`]. The
+/// signatures are ASCII armored, as if created by `gpg --detach-sign --armor`.
+pub fn sign<R: Read + Send>(key: &Key, files: Vec<(&str, R)>) -> Result<Vec<(String, Vec<u8>)>> {
+    files
+        .into_par_iter()
+        .map(|(name, reader)| {
+            let signature = match key.signing_subkey() {
+                Some(subkey) => sign_data(subkey, &key.passphrase, reader),
+                None => sign_data(&key.key.primary_key, &key.passphrase, reader),
+            }
+            .with_context(|| format!("failed creating signature for {name:?}"))?;
+
+            info!(%name, "signed file");
+
+            Ok((format!("{name}.asc"), signature.into_bytes()))
+        })
+        .collect()
+}
+
+fn sign_data(
+    key: &impl SigningKey,
+    passphrase: &Password,
+    data: impl Read,
+) -> pgp::errors::Result<String> {
+    DetachedSignature::sign_binary_data(rand::thread_rng(), key, passphrase, HASH_ALGORITHM, data)?
+        .to_armored_string(ArmorOptions::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use pgp::composed::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder};
+
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() -> Result<()> {
+        let key = SecretKeyParamsBuilder::default()
+            .key_type(KeyType::Ed25519)
+            .can_certify(true)
+            .can_sign(true)
+            .primary_user_id("Test <test@example.com>".into())
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::Ed25519)
+                    .can_sign(true)
+                    .build()?,
+            )
+            .build()?
+            .generate(rand::thread_rng())?;
+        let armored = key.to_armored_string(ArmorOptions::default())?;
+
+        let key = import_key(&armored, None)?;
+        let data = b"checksums".as_slice();
+        let files = sign(&key, vec![("checksums.sha256", data)])?;
+
+        assert_eq!(1, files.len());
+        assert_eq!("checksums.sha256.asc", files[0].0);
+
+        let (signature, _) = DetachedSignature::from_string(std::str::from_utf8(&files[0].1)?)?;
+        let public = key.key.to_public_key();
+        signature.verify(&public.public_subkeys[0].key, data)?;
+        assert!(signature
+            .verify(&public.public_subkeys[0].key, b"other")
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+//! Selection between the available OpenPGP implementations.
+
+use std::io::Read;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::{gpg, native};
+
+/// Implementation that is used to create signatures.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// The system-installed `gpg` binary.
+    #[default]
+    Gpg,
+    /// The built-in OpenPGP implementation, not requiring any external tools.
+    Native,
+}
+
+/// A secret key that was imported into one of the [`Backend`]s and is ready to sign files.
+pub enum Signer<'a> {
+    /// Key imported into GPG's keyring.
+    Gpg {
+        /// Identifier of the key inside the keyring.
+        key_id: gpg::KeyId,
+        /// Passphrase, required for every signing operation.
+        passphrase: Option<&'a str>,
+    },
+    /// Key loaded into memory.
+    Native(Box<native::Key>),
+}
+
+impl<'a> Signer<'a> {
+    /// Import the ASCII armored secret key into the given backend.
+    pub fn import(backend: Backend, key: &str, passphrase: Option<&'a str>) -> Result<Self> {
+        Ok(match backend {
+            Backend::Gpg => Self::Gpg {
+                key_id: gpg::import_key(key, passphrase)?,
+                passphrase,
+            },
+            Backend::Native => Self::Native(Box::new(native::import_key(key, passphrase)?)),
+        })
+    }
+
+    /// Create detached, ASCII armored signatures for all the given files.
+    ///
+    /// The files are a tuple of file name and content, and the returned signatures are named after
+    /// the file with an additional `.asc` extension.
+    pub fn sign<R: Read + Send>(&self, files: Vec<(&str, R)>) -> Result<Vec<(String, Vec<u8>)>> {
+        match self {
+            Self::Gpg { key_id, passphrase } => gpg::sign(key_id, *passphrase, files),
+            Self::Native(key) => native::sign(key, files),
+        }
+    }
+
+    /// Export the public part of the key in ASCII armored form.
+    pub fn export_key(&self) -> Result<String> {
+        match self {
+            Self::Gpg { key_id, .. } => gpg::export_key(key_id),
+            Self::Native(key) => native::export_key(key),
+        }
+    }
+
+    /// Get the fingerprint and numeric public key algorithm identifier of the key.
+    pub fn key_details(&self) -> Result<(String, u8)> {
+        match self {
+            Self::Gpg { key_id, .. } => gpg::key_details(key_id),
+            Self::Native(key) => Ok(native::key_details(key)),
+        }
+    }
+
+    /// Remove any traces of the key from the backend.
+    pub fn close(self) -> Result<()> {
+        match self {
+            Self::Gpg { key_id, .. } => gpg::delete_key(&key_id),
+            Self::Native(_) => Ok(()),
+        }
+    }
+}